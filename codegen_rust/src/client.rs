@@ -9,7 +9,7 @@ use hir::{qualified_env_var, AuthLocation, AuthStrategy, HirSpec, Language, Oaut
 use hir::{Config, Operation};
 use libninja_macro::rfunction;
 use mir::{import, Class, Field, File, Function, Ident, Item, Module, Visibility};
-use mir_rust::{ToRustCode, ToRustIdent, ToRustType};
+use mir_rust::{deprecated_to_tokens, ToRustCode, ToRustIdent, ToRustType};
 
 /// Generates the client code for a given OpenAPI specification.
 pub fn make_lib_rs(spec: &HirSpec, extras: &Extras, cfg: &Config) -> File<TokenStream> {
@@ -27,12 +27,84 @@ pub fn make_lib_rs(spec: &HirSpec, extras: &Extras, cfg: &Config) -> File<TokenS
         })
         .unwrap_or_default();
 
-    let fluent_request = quote! {
-        #[derive(Clone)]
-        pub struct FluentRequest<'a, T> {
-            pub(crate) client: &'a #client_name,
-            pub params: T,
+    let server = if cfg.server {
+        quote! {
+            #[cfg(feature = "server")]
+            pub mod server;
+        }
+    } else {
+        TokenStream::new()
+    };
+
+    let webhook = if spec.webhooks.is_empty() {
+        TokenStream::new()
+    } else {
+        quote! {
+            pub mod webhook;
+        }
+    };
+
+    let fluent_request = fluent_request(&client_name);
+    let validation_error = if extras.validation {
+        quote! {
+            /// A value that breaks a constraint in the API spec, returned by `validate()`.
+            #[derive(Debug, Clone, PartialEq, Eq)]
+            pub struct ValidationError {
+                pub field: &'static str,
+                pub message: &'static str,
+            }
+
+            impl std::fmt::Display for ValidationError {
+                fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                    write!(f, "{} {}", self.field, self.message)
+                }
+            }
+
+            impl std::error::Error for ValidationError {}
         }
+    } else {
+        TokenStream::new()
+    };
+    let request_error = if extras.validates_requests(cfg) {
+        quote! {
+            /// An error from a request, which isn't sent if its params fail `validate()`.
+            #[derive(Debug)]
+            pub enum RequestError {
+                Validation(ValidationError),
+                Http(httpclient::InMemoryError),
+            }
+
+            impl std::fmt::Display for RequestError {
+                fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                    match self {
+                        Self::Validation(e) => write!(f, "Invalid request: {}", e),
+                        Self::Http(e) => write!(f, "{}", e),
+                    }
+                }
+            }
+
+            impl std::error::Error for RequestError {}
+
+            impl From<ValidationError> for RequestError {
+                fn from(e: ValidationError) -> Self {
+                    Self::Validation(e)
+                }
+            }
+
+            impl From<httpclient::InMemoryError> for RequestError {
+                fn from(e: httpclient::InMemoryError) -> Self {
+                    Self::Http(e)
+                }
+            }
+
+            impl From<serde_json::Error> for RequestError {
+                fn from(e: serde_json::Error) -> Self {
+                    Self::Http(e.into())
+                }
+            }
+        }
+    } else {
+        TokenStream::new()
     };
     let base64_import = extras
         .basic_auth
@@ -55,6 +127,7 @@ pub fn make_lib_rs(spec: &HirSpec, extras: &Extras, cfg: &Config) -> File<TokenS
         })
         .unwrap_or_default();
     let static_shared_http_client = static_shared_http_client();
+    let retry_policy = retry_policy();
     let default_http_client = fn_default_http_client(spec, cfg);
     let oauth = spec
         .security
@@ -67,8 +140,13 @@ pub fn make_lib_rs(spec: &HirSpec, extras: &Extras, cfg: &Config) -> File<TokenS
     let shared_oauth2_flow = oauth
         .map(|auth| shared_oauth2_flow(auth, spec, cfg))
         .unwrap_or_default();
+    let mut attributes = vec![];
+    if extras.deprecated {
+        // Deprecated items are still used within the crate. Only the library's users should get warnings.
+        attributes.push(quote! { #![allow(deprecated)] });
+    }
     File {
-        attributes: vec![],
+        attributes,
         doc: None,
         imports: vec![
             import!(std::sync, OnceLock),
@@ -78,10 +156,15 @@ pub fn make_lib_rs(spec: &HirSpec, extras: &Extras, cfg: &Config) -> File<TokenS
         items: vec![
             Item::Block(base64_import),
             Item::Block(serde),
+            Item::Block(server),
+            Item::Block(webhook),
             Item::Fn(default_http_client),
             Item::Block(static_shared_http_client),
             Item::Block(shared_oauth2_flow),
             Item::Block(fluent_request),
+            Item::Block(retry_policy),
+            Item::Block(validation_error),
+            Item::Block(request_error),
             Item::Class(struct_Client),
             Item::Block(impl_Client),
             Item::Block(security),
@@ -113,12 +196,14 @@ fn build_Client_from_env(spec: &HirSpec, opt: &Config) -> Function<TokenStream>
             Self {
                 client: shared_http_client(),
                 authentication: #auth_struct::from_env(),
+                retry: RetryPolicy::default(),
             }
         }
     } else {
         quote! {
             Self {
-                client: shared_http_client()
+                client: shared_http_client(),
+                retry: RetryPolicy::default(),
             }
         }
     };
@@ -130,7 +215,8 @@ fn build_Client_with_auth(_spec: &HirSpec, opt: &Config) -> Function<TokenStream
     rfunction!(pub with_auth(authentication: #auth_struct) -> Self {
         Self {
             client: shared_http_client(),
-            authentication
+            authentication,
+            retry: RetryPolicy::default(),
         }
     })
 }
@@ -141,6 +227,7 @@ fn build_Client_new(_spec: &HirSpec, opt: &Config) -> Function<TokenStream> {
         Self {
             client: Cow::Owned(client),
             authentication,
+            retry: RetryPolicy::default(),
         }
     };
     rfunction!(pub new(client: Client, authentication: #auth_struct) -> Self).body(body)
@@ -161,6 +248,11 @@ pub fn struct_Client(spec: &HirSpec, opt: &Config) -> Class<TokenStream> {
             ..Field::default()
         });
     }
+    instance_fields.push(Field {
+        name: Ident::new("retry"),
+        ty: quote!(RetryPolicy),
+        ..Field::default()
+    });
 
     let mut methods = vec![build_Client_from_env(spec, opt)];
     if spec.has_security() {
@@ -168,7 +260,8 @@ pub fn struct_Client(spec: &HirSpec, opt: &Config) -> Class<TokenStream> {
     } else {
         methods.push(rfunction!(pub new() -> Self {
             Self {
-                client: shared_http_client()
+                client: shared_http_client(),
+                retry: RetryPolicy::default(),
             }
         }));
     }
@@ -185,7 +278,16 @@ pub fn struct_Client(spec: &HirSpec, opt: &Config) -> Class<TokenStream> {
     }
 }
 
-pub fn build_api_client_method(operation: &Operation) -> TokenStream {
+/// The module under `request` for the operation's tag, and so its sub-client, if tag clients are on.
+pub fn tag_module(operation: &Operation, cfg: &Config) -> Option<String> {
+    operation.tag().filter(|_| cfg.tag_clients).map(mir_rust::sanitize)
+}
+
+pub fn tag_client_name(tag: &str) -> Ident {
+    format!("{tag} client").to_rust_struct()
+}
+
+pub fn build_api_client_method(operation: &Operation, spec: &HirSpec, cfg: &Config) -> TokenStream {
     let use_struct = operation.use_required_struct(Language::Rust);
 
     let fn_args = if use_struct {
@@ -232,12 +334,20 @@ pub fn build_api_client_method(operation: &Operation) -> TokenStream {
 
     let doc = operation.doc.clone().to_rust_code();
     let request_struct = operation.request_struct_name().to_rust_struct();
-    let name = &operation.name.to_rust_ident();
+    let deprecated = deprecated_to_tokens(operation.deprecated);
+    // Sub-clients borrow the client, so their requests can outlive them.
+    let (name, lifetime, client) = if tag_module(operation, cfg).is_some() {
+        (spec.tag_method_name(operation).to_rust_ident(), quote!('a), quote!(self.client))
+    } else {
+        (operation.name.to_rust_ident(), quote!('_), quote!(self))
+    };
     quote! {
         #doc
-        pub fn #name(&self, #(#fn_args),*) -> FluentRequest<'_, #request_struct> {
+        #deprecated
+        pub fn #name(&self, #(#fn_args),*) -> FluentRequest<#lifetime, #request_struct> {
             FluentRequest {
-                client: self,
+                client: #client,
+                options: RequestOptions::default(),
                 params: #request_struct {
                     #(#struct_field_values,)*
                 }
@@ -334,6 +444,12 @@ pub fn impl_Client(spec: &HirSpec, opt: &Config) -> TokenStream {
 
     quote! {
         impl #client_struct_name {
+            /// Replace the default [`RetryPolicy`] for requests made with this client.
+            pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+                self.retry = retry;
+                self
+            }
+
             #authenticate
         }
     }
@@ -485,6 +601,202 @@ fn static_shared_http_client() -> TokenStream {
     }
 }
 
+/// `FluentRequest`, which every request method on the client returns, and the per-request options it sets.
+fn fluent_request(client_name: &Ident) -> TokenStream {
+    quote! {
+        #[derive(Clone)]
+        pub struct FluentRequest<'a, T> {
+            pub(crate) client: &'a #client_name,
+            pub params: T,
+            pub options: RequestOptions,
+        }
+
+        impl<T> FluentRequest<'_, T> {
+            /// Give up on the request if a response takes longer than `timeout`. Each retry gets the whole timeout.
+            pub fn timeout(mut self, timeout: std::time::Duration) -> Self {
+                self.options.timeout = Some(timeout);
+                self
+            }
+
+            /// Send a header that isn't in the API spec.
+            pub fn header(mut self, name: &str, value: &str) -> Self {
+                self.options.headers.push((name.to_owned(), value.to_owned()));
+                self
+            }
+
+            /// Send the request to `base_url` instead of the client's base URL.
+            pub fn base_url(mut self, base_url: &str) -> Self {
+                self.options.base_url = Some(base_url.to_owned());
+                self
+            }
+
+            /// Send a query parameter that isn't in the API spec.
+            pub fn query_extra(mut self, key: &str, value: &str) -> Self {
+                self.options.query.push((key.to_owned(), value.to_owned()));
+                self
+            }
+        }
+
+        /// A response's status and headers, along with its parsed body, from `send_with_response()`. `R` is the
+        /// request type, so the response has methods for the headers its operation declares in the API spec.
+        #[derive(Debug, Clone)]
+        pub struct Response<T, R> {
+            pub status: http::StatusCode,
+            pub headers: http::HeaderMap,
+            pub body: T,
+            request: std::marker::PhantomData<R>,
+        }
+
+        impl<T, R> Response<T, R> {
+            pub(crate) fn new(status: http::StatusCode, headers: http::HeaderMap, body: T) -> Self {
+                Self {
+                    status,
+                    headers,
+                    body,
+                    request: std::marker::PhantomData,
+                }
+            }
+        }
+
+        /// Settings for a single request, which are set with the methods on [`FluentRequest`].
+        #[derive(Debug, Clone, Default)]
+        pub struct RequestOptions {
+            pub timeout: Option<std::time::Duration>,
+            pub headers: Vec<(String, String)>,
+            pub base_url: Option<String>,
+            pub query: Vec<(String, String)>,
+        }
+
+        impl RequestOptions {
+            pub(crate) fn url<'u>(&self, path: &'u str) -> Cow<'u, str> {
+                match &self.base_url {
+                    Some(base_url) => Cow::Owned(format!("{}{}", base_url.trim_end_matches('/'), path)),
+                    None => Cow::Borrowed(path),
+                }
+            }
+
+            /// Add the extra headers and query parameters to `r`, and send it.
+            pub(crate) async fn send(
+                &self,
+                mut r: httpclient::RequestBuilder<'_>,
+            ) -> httpclient::InMemoryResult<httpclient::InMemoryResponse> {
+                for (name, value) in &self.headers {
+                    r = r.header(name.as_str(), value);
+                }
+                for (key, value) in &self.query {
+                    r = r.query(key.as_str(), value);
+                }
+                let Some(timeout) = self.timeout else {
+                    return r.await;
+                };
+                match tokio::time::timeout(timeout, r).await {
+                    Ok(result) => result,
+                    Err(_) => {
+                        let e = std::io::Error::new(std::io::ErrorKind::TimedOut, "The request timed out");
+                        Err(httpclient::Error::Protocol(httpclient::ProtocolError::IoError(e)))
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn retry_policy() -> TokenStream {
+    quote! {
+        /// How requests are retried after a connection error, `408 Request Timeout`, `429 Too Many Requests` or `5xx`
+        /// response. The wait doubles with each retry, with random jitter, unless the response has a `Retry-After`
+        /// header. Operations that aren't idempotent, e.g. POST, are only retried if the API spec or
+        /// `retry_non_idempotent` says so.
+        #[derive(Debug, Clone)]
+        pub struct RetryPolicy {
+            /// Retries after the first attempt. `0` turns retrying off.
+            pub max_retries: u32,
+            /// The wait before the first retry.
+            pub initial_backoff: std::time::Duration,
+            pub max_backoff: std::time::Duration,
+            /// Also retry operations that aren't idempotent, which could repeat their effect.
+            pub retry_non_idempotent: bool,
+        }
+
+        impl Default for RetryPolicy {
+            fn default() -> Self {
+                Self {
+                    max_retries: 3,
+                    initial_backoff: std::time::Duration::from_millis(500),
+                    max_backoff: std::time::Duration::from_secs(30),
+                    retry_non_idempotent: false,
+                }
+            }
+        }
+
+        impl RetryPolicy {
+            /// Send each request once.
+            pub fn none() -> Self {
+                Self {
+                    max_retries: 0,
+                    ..Self::default()
+                }
+            }
+
+            /// Send the request built by `request`, and build and send it again while it fails with a retryable
+            /// error. `idempotent` and `max_retries` are the operation's, from its method and the API spec.
+            pub(crate) async fn send<R>(
+                &self,
+                idempotent: bool,
+                max_retries: Option<u32>,
+                mut request: impl FnMut() -> R,
+            ) -> httpclient::InMemoryResult<httpclient::InMemoryResponse>
+            where
+                R: std::future::IntoFuture<Output = httpclient::InMemoryResult<httpclient::InMemoryResponse>>,
+            {
+                let max_retries = if idempotent || self.retry_non_idempotent {
+                    max_retries.unwrap_or(self.max_retries)
+                } else {
+                    0
+                };
+                let mut attempt = 0;
+                loop {
+                    let result = request().await;
+                    if attempt >= max_retries {
+                        return result;
+                    }
+                    let wait = match &result {
+                        Err(httpclient::Error::HttpError(res)) if is_retryable(res.status().as_u16()) => {
+                            retry_after(res).unwrap_or_else(|| self.backoff(attempt))
+                        }
+                        Err(httpclient::Error::Protocol(_)) => self.backoff(attempt),
+                        _ => return result,
+                    };
+                    tokio::time::sleep(wait).await;
+                    attempt += 1;
+                }
+            }
+
+            /// `initial_backoff * 2^attempt`, up to `max_backoff`, of which a random half to all is waited.
+            fn backoff(&self, attempt: u32) -> std::time::Duration {
+                use std::hash::{BuildHasher, Hasher};
+                let backoff = self
+                    .initial_backoff
+                    .saturating_mul(2u32.saturating_pow(attempt))
+                    .min(self.max_backoff);
+                // Each `RandomState` has new random keys, so this is a random number without depending on `rand`.
+                let random = std::collections::hash_map::RandomState::new().build_hasher().finish();
+                backoff / 2 + (backoff / 2).mul_f64(random as f64 / u64::MAX as f64)
+            }
+        }
+
+        fn is_retryable(status: u16) -> bool {
+            matches!(status, 408 | 429 | 500..=599)
+        }
+
+        /// The wait in a `Retry-After` header, when it's a number of seconds rather than a date.
+        fn retry_after(res: &httpclient::InMemoryResponse) -> Option<std::time::Duration> {
+            let seconds = res.headers().get("retry-after")?.to_str().ok()?.trim().parse().ok()?;
+            Some(std::time::Duration::from_secs(seconds))
+        }
+    }
+}
+
 fn shared_oauth2_flow(auth: &Oauth2Auth, _spec: &HirSpec, cfg: &Config) -> TokenStream {
     let service_name = cfg.name.as_str();
 
@@ -517,3 +829,67 @@ fn shared_oauth2_flow(auth: &Oauth2Auth, _spec: &HirSpec, cfg: &Config) -> Token
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extras::calculate_extras;
+    use hir::{Constraints, Location, Parameter};
+    use mir::Ty;
+    use mir_rust::format_code;
+
+    #[test]
+    fn test_timeout_error() {
+        let code = format_code(fluent_request(&Ident("PetStoreClient".to_string())));
+        assert!(code.contains("tokio::time::timeout(timeout, r)"));
+        assert!(code.contains("std::io::ErrorKind::TimedOut"));
+        assert!(code.contains("Err(httpclient::Error::Protocol(httpclient::ProtocolError::IoError(e)))"));
+        assert!(!code.contains("serde_json"));
+    }
+
+    #[test]
+    fn test_request_option_methods() {
+        let code = format_code(fluent_request(&Ident("PetStoreClient".to_string())));
+        assert!(code.contains("pub fn timeout(mut self, timeout: std::time::Duration) -> Self {"));
+        assert!(code.contains("self.options.timeout = Some(timeout);"));
+        assert!(code.contains("pub fn header(mut self, name: &str, value: &str) -> Self {"));
+        assert!(code.contains("self.options.headers.push((name.to_owned(), value.to_owned()));"));
+        assert!(code.contains("pub fn base_url(mut self, base_url: &str) -> Self {"));
+        assert!(code.contains("self.options.base_url = Some(base_url.to_owned());"));
+        assert!(code.contains("pub fn query_extra(mut self, key: &str, value: &str) -> Self {"));
+        assert!(code.contains("self.options.query.push((key.to_owned(), value.to_owned()));"));
+        assert!(code.contains("r = r.header(name.as_str(), value);"));
+        assert!(code.contains("r = r.query(key.as_str(), value);"));
+    }
+
+    #[test]
+    fn test_request_error() {
+        let mut spec = HirSpec::default();
+        spec.operations.push(Operation {
+            name: "listUsers".to_string(),
+            parameters: vec![Parameter {
+                location: Location::Query,
+                constraints: Constraints {
+                    max_length: Some(10),
+                    ..Constraints::default()
+                },
+                ..Parameter::path("name", Ty::String)
+            }],
+            ..Operation::default()
+        });
+        let extras = calculate_extras(&spec);
+        let code = format_code(make_lib_rs(&spec, &extras, &Config::default()).to_rust_code());
+        assert!(code.contains(
+            "pub enum RequestError {\n    Validation(ValidationError),\n    Http(httpclient::InMemoryError),\n}"
+        ));
+        assert!(code.contains("impl From<ValidationError> for RequestError {"));
+
+        let cfg = Config {
+            skip_validation: true,
+            ..Config::default()
+        };
+        let code = format_code(make_lib_rs(&spec, &extras, &cfg).to_rust_code());
+        assert!(code.contains("pub struct ValidationError"));
+        assert!(!code.contains("RequestError"));
+    }
+}
@@ -1,16 +1,16 @@
-use crate::write_rust;
+use crate::client::tag_module;
+use crate::request::builder_method_name;
+use crate::{write_rust, Modified};
 use anyhow::Result;
 use hir::{Config, HirSpec, Language, Operation};
 use libninja_macro::rfunction;
 use mir::{import, File, Function, Item};
 use mir_rust::{to_rust_example_value, ToRustCode, ToRustIdent};
 use proc_macro2::TokenStream;
-use quote::quote;
-use std::collections::HashSet;
+use quote::{quote, ToTokens};
 use std::fs;
-use std::path::PathBuf;
 
-pub fn write_examples_folder(spec: &HirSpec, config: &Config, modified: &mut HashSet<PathBuf>) -> Result<()> {
+pub fn write_examples_folder(spec: &HirSpec, config: &Config, modified: &mut Modified) -> Result<()> {
     let path = config.dest.join("examples");
     fs::create_dir_all(&path)?;
     for operation in &spec.operations {
@@ -55,7 +55,7 @@ pub fn generate_example(operation: &Operation, cfg: &Config, spec: &HirSpec) ->
         .optional_args()
         .into_iter()
         .map(|p| {
-            let ident = p.name.to_rust_ident();
+            let ident = builder_method_name(p);
             let value = to_rust_example_value(&p.ty, &p.name, spec, true);
             Ok(quote! {
                 .#ident(#value)
@@ -68,16 +68,27 @@ pub fn generate_example(operation: &Operation, cfg: &Config, spec: &HirSpec) ->
     ];
     if use_required {
         let struct_name = operation.required_struct_name();
-        let file_name = operation.file_name();
+        let file_name = match tag_module(operation, cfg) {
+            Some(tag) => format!("{tag}::{}", operation.file_name()),
+            None => operation.file_name(),
+        };
         let package_name = cfg.package_name();
         imports.push(import!(format!("{package_name}::request::{file_name}::{struct_name}")));
     }
-    let operation = operation.name.to_rust_ident();
+    let deprecated = operation.deprecated;
+    let method = match tag_module(operation, cfg) {
+        Some(tag) => {
+            let tag = tag.to_rust_ident();
+            let name = spec.tag_method_name(operation).to_rust_ident();
+            quote!(#tag().#name)
+        }
+        None => operation.name.to_rust_ident().to_token_stream(),
+    };
     let client = cfg.client_name();
     let mut main: Function<TokenStream> = rfunction!(async main() {
        let client = #client::from_env();
         #(#declarations)*
-        let response = client.#operation(#fn_args)
+        let response = client.#method(#fn_args)
             #(#optionals)*
             .await
             .unwrap();
@@ -85,8 +96,12 @@ pub fn generate_example(operation: &Operation, cfg: &Config, spec: &HirSpec) ->
     });
     main.attributes.push(quote!(#[tokio::main]));
 
+    let mut attributes = vec![quote! {#![allow(unused_imports)]}];
+    if deprecated {
+        attributes.push(quote! {#![allow(deprecated)]});
+    }
     Ok(File {
-        attributes: vec![quote! {#![allow(unused_imports)]}],
+        attributes,
         imports,
         items: vec![Item::Fn(main)],
         ..File::default()
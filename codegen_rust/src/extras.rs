@@ -1,5 +1,6 @@
-use hir::HirSpec;
-use mir::{DateSerialization, IntegerSerialization};
+use hir::{Config, Constraints, HirSpec};
+use mir::{DateSerialization, IntegerSerialization, StringFormat, Ty};
+use std::collections::HashSet;
 
 #[derive(Debug)]
 pub struct Extras {
@@ -10,31 +11,86 @@ pub struct Extras {
     pub integer_date_serialization: bool,
     pub basic_auth: bool,
     pub oauth2: bool,
+    /// String formats used anywhere in the spec. Each pulls in its crate (e.g. uuid, url).
+    pub string_formats: HashSet<StringFormat>,
+    /// Some field or parameter has constraints, so models or requests have a `validate()` method.
+    pub validation: bool,
+    /// Some string field or parameter has a `pattern`, so `validate()` checks it with `regex`.
+    pub regex: bool,
+    /// Something is marked `deprecated`, so the crate itself allows using deprecated items.
+    pub deprecated: bool,
 }
 
 impl Extras {
     pub fn needs_serde(&self) -> bool {
-        self.null_as_zero || self.integer_date_serialization || self.option_i64_str
+        self.null_as_zero
+            || self.integer_date_serialization
+            || self.option_i64_str
+            || self.base64_bytes()
+            || self.binary_string()
+            || self.iso8601_duration()
+    }
+
+    /// Requests call `validate()` before sending, so they return `crate::RequestError`, which has a variant for it.
+    pub fn validates_requests(&self, cfg: &Config) -> bool {
+        self.validation && !cfg.skip_validation
+    }
+
+    pub fn base64_bytes(&self) -> bool {
+        self.string_formats.contains(&StringFormat::Byte)
+    }
+
+    pub fn binary_string(&self) -> bool {
+        self.string_formats.contains(&StringFormat::Binary)
+    }
+
+    pub fn iso8601_duration(&self) -> bool {
+        self.string_formats.contains(&StringFormat::Duration)
+    }
+}
+
+/// Whether `validate()` checks a `pattern` for this field or parameter, which it only does for strings.
+fn checks_pattern(ty: &Ty, constraints: &Constraints) -> bool {
+    matches!(ty, Ty::String) && constraints.pattern.is_some()
+}
+
+fn collect_string_formats(ty: &Ty, formats: &mut HashSet<StringFormat>) {
+    match ty {
+        Ty::FormattedString(format) => {
+            formats.insert(*format);
+        }
+        Ty::Array(inner) | Ty::HashMap(inner) => collect_string_formats(inner, formats),
+        _ => {}
     }
 }
 
 pub fn calculate_extras(spec: &HirSpec) -> Extras {
-    use mir::Ty;
     let mut null_as_zero = false;
     let mut date_serialization = false;
     let mut currency = false;
     let mut integer_date_serialization = false;
     let mut option_i64_str = false;
+    let mut string_formats = HashSet::new();
+    let mut validation = false;
+    let mut regex = false;
+    let mut deprecated = false;
     for (_, record) in &spec.schemas {
+        deprecated |= record.deprecated();
         for field in record.fields() {
+            collect_string_formats(&field.ty, &mut string_formats);
+            validation |= !field.constraints.is_empty();
+            regex |= checks_pattern(&field.ty, &field.constraints);
+            deprecated |= field.deprecated;
             match &field.ty {
                 Ty::Integer {
                     ser: IntegerSerialization::NullAsZero,
+                    ..
                 } => {
                     null_as_zero = true;
                 }
                 Ty::Integer {
                     ser: IntegerSerialization::String,
+                    ..
                 } => {
                     option_i64_str = true;
                 }
@@ -54,6 +110,16 @@ pub fn calculate_extras(spec: &HirSpec) -> Extras {
             }
         }
     }
+    for operation in &spec.operations {
+        collect_string_formats(&operation.ret, &mut string_formats);
+        deprecated |= operation.deprecated;
+        for param in &operation.parameters {
+            collect_string_formats(&param.ty, &mut string_formats);
+            validation |= !param.constraints.is_empty();
+            regex |= checks_pattern(&param.ty, &param.constraints);
+            deprecated |= param.deprecated;
+        }
+    }
     let basic_auth = spec.has_basic_auth();
     let oauth2 = spec.oauth2_auth().is_some();
     Extras {
@@ -64,5 +130,9 @@ pub fn calculate_extras(spec: &HirSpec) -> Extras {
         option_i64_str,
         basic_auth,
         oauth2,
+        string_formats,
+        validation,
+        regex,
+        deprecated,
     }
 }
@@ -0,0 +1,181 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::{anyhow, Result};
+use convert_case::{Case, Casing};
+use hir::{Config, HirSpec, Operation};
+use proc_macro2::TokenStream;
+use quote::quote;
+use toml_edit::{value, Array, DocumentMut, Table};
+
+/// The cargo feature for a tag.
+pub fn feature_name(tag: &str) -> String {
+    tag.to_case(Case::Kebab)
+}
+
+/// `#[cfg(...)]` for code that any of the tags need. Nothing if there are no tags, or `--tag-features` is off.
+pub fn cfg_features<'a>(tags: impl IntoIterator<Item = &'a str>, cfg: &Config) -> TokenStream {
+    if !cfg.tag_features {
+        return TokenStream::new();
+    }
+    let features = tags.into_iter().map(feature_name).collect::<BTreeSet<_>>();
+    match features.len() {
+        0 => TokenStream::new(),
+        1 => {
+            let feature = features.first();
+            quote!(#[cfg(feature = #feature)])
+        }
+        _ => {
+            let features = features.iter();
+            quote!(#[cfg(any(#(feature = #features),*))])
+        }
+    }
+}
+
+/// `#[cfg(...)]` for the code of an operation, behind the feature of its tag.
+pub fn operation_cfg(operation: &Operation, cfg: &Config) -> TokenStream {
+    cfg_features(operation.tag(), cfg)
+}
+
+/// The `#[cfg(...)]` for each model, which is only compiled when an operation that uses it is.
+pub struct ModelFeatures<'a> {
+    tags: BTreeMap<&'a str, BTreeSet<&'a str>>,
+    cfg: &'a Config,
+}
+
+impl<'a> ModelFeatures<'a> {
+    pub fn new(spec: &'a HirSpec, cfg: &'a Config) -> Self {
+        let tags = if cfg.tag_features {
+            spec.model_tags()
+        } else {
+            BTreeMap::new()
+        };
+        Self { tags, cfg }
+    }
+
+    pub fn cfg(&self, model: &str) -> TokenStream {
+        cfg_features(self.tags.get(model).into_iter().flatten().copied(), self.cfg)
+    }
+}
+
+/// Add a feature per tag to the `[features]` table of the library's `Cargo.toml`, all of them to `full`, and `full`
+/// to `default`, unless it's already set. Examples of tagged operations get `required-features`. The features listed
+/// in `full` by the last run, and examples needing only those, are replaced, so the rest of the file is kept.
+pub fn add_cargo_features(manifest: &mut DocumentMut, spec: &HirSpec, cfg: &Config) -> Result<()> {
+    let tags = spec
+        .operations_by_tag()
+        .into_keys()
+        .map(feature_name)
+        .collect::<BTreeSet<_>>();
+
+    let features = manifest
+        .entry("features")
+        .or_insert(toml_edit::table())
+        .as_table_mut()
+        .ok_or_else(|| anyhow!("`features` isn't a table"))?;
+    let generated = features
+        .get("full")
+        .and_then(|f| f.as_array())
+        .map(|a| {
+            a.iter()
+                .filter_map(|f| f.as_str())
+                .map(String::from)
+                .collect::<BTreeSet<_>>()
+        })
+        .unwrap_or_default();
+    for feature in &generated {
+        features.remove(feature);
+    }
+    if !features.contains_key("default") {
+        features.insert("default", value(Array::from_iter(["full"])));
+    }
+    features.insert("full", value(Array::from_iter(&tags)));
+    for tag in &tags {
+        features.insert(tag, value(Array::new()));
+    }
+
+    let mut examples = manifest
+        .remove("example")
+        .and_then(|e| e.into_array_of_tables().ok())
+        .unwrap_or_default();
+    examples.retain(|example| {
+        let required = example.get("required-features").and_then(|f| f.as_array());
+        !required.is_some_and(|r| r.iter().all(|f| f.as_str().is_some_and(|f| generated.contains(f))))
+    });
+    if cfg.build_examples {
+        for operation in &spec.operations {
+            let Some(tag) = operation.tag() else {
+                continue;
+            };
+            let mut example = Table::new();
+            example.insert("name", value(operation.file_name()));
+            example.insert("required-features", value(Array::from_iter([feature_name(tag)])));
+            examples.push(example);
+        }
+    }
+    if !examples.is_empty() {
+        manifest.insert("example", toml_edit::Item::ArrayOfTables(examples));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::extras::calculate_extras;
+    use crate::manifest::write_manifest;
+    use crate::Modified;
+
+    #[test]
+    fn test_cfg_features() {
+        let cfg = Config {
+            tag_features: true,
+            ..Config::default()
+        };
+        assert_eq!(cfg_features(None, &cfg).to_string(), "");
+        assert_eq!(
+            cfg_features(["billing_info"], &cfg).to_string(),
+            quote!(#[cfg(feature = "billing-info")]).to_string()
+        );
+        assert_eq!(
+            cfg_features(["site", "account", "site"], &cfg).to_string(),
+            quote!(#[cfg(any(feature = "account", feature = "site"))]).to_string()
+        );
+        assert_eq!(cfg_features(["site"], &Config::default()).to_string(), "");
+    }
+
+    #[test]
+    fn test_write_cargo_features() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("Cargo.toml"),
+            "[package]\nname = \"pay\"\n\n[features]\nserver = [\"dep:axum\"]\nfull = [\"old\"]\nold = []\n\n\
+            [[example]]\nname = \"old_example\"\nrequired-features = [\"old\"]\n\n\
+            [[example]]\nname = \"mine\"\nrequired-features = [\"server\"]\n",
+        )
+        .unwrap();
+        let mut spec = HirSpec::default();
+        spec.operations.push(Operation {
+            name: "ListAccounts".to_string(),
+            tags: vec!["account".to_string()],
+            ..Operation::default()
+        });
+        let cfg = Config {
+            dest: dir.path().to_path_buf(),
+            build_examples: true,
+            tag_features: true,
+            ..Config::default()
+        };
+        write_manifest(&spec, &cfg, &calculate_extras(&spec), &mut Modified::default()).unwrap();
+        let manifest = fs::read_to_string(dir.path().join("Cargo.toml")).unwrap();
+        let manifest = manifest.parse::<DocumentMut>().unwrap();
+        let features = manifest["features"].as_table().unwrap();
+        let keys = features.iter().map(|(k, _)| k).collect::<Vec<_>>();
+        assert_eq!(keys, ["server", "full", "default", "account"]);
+        assert_eq!(features["full"].to_string().trim(), r#"["account"]"#);
+        let examples = manifest["example"].as_array_of_tables().unwrap();
+        let names = examples.iter().map(|e| e["name"].as_str().unwrap()).collect::<Vec<_>>();
+        assert_eq!(names, ["mine", "list_accounts"]);
+    }
+}
@@ -1,9 +1,15 @@
 pub mod client;
 mod example;
 mod extras;
+mod features;
+mod manifest;
+mod mock;
 mod model;
 pub mod request;
+mod roundtrip;
 mod serde;
+mod server;
+mod webhook;
 
 use anyhow::Result;
 use client::make_lib_rs;
@@ -12,43 +18,111 @@ use example::write_examples_folder;
 use extras::calculate_extras;
 use hir::Config;
 use hir::HirSpec;
+use manifest::write_manifest;
 use mir::{File, Item};
 use mir_rust::{format_code, ToRustCode};
+use mock::write_mock_server;
 use model::write_model_module;
 use proc_macro2::TokenStream;
+use rayon::prelude::*;
 use request::write_request_module;
+use roundtrip::{write_roundtrip_tests, ROUNDTRIP_TESTS};
 use serde::write_serde_module;
+use server::write_server_module;
+use webhook::write_webhook_module;
 use std::{
     collections::HashSet,
     fs,
     path::{Path, PathBuf},
 };
 use std_ext::PathExt;
+use tracing::debug;
 
-pub type Modified = HashSet<PathBuf>;
+/// The files generated so far, which `remove_old_files` keeps, and which of them changed.
+#[derive(Debug, Default)]
+pub struct Modified {
+    generated: HashSet<PathBuf>,
+    written: Vec<PathBuf>,
+}
+
+impl Modified {
+    /// Write a generated file, unless it already has this content.
+    fn write(&mut self, path: &Path, content: &str) -> std::io::Result<()> {
+        self.generated.insert(path.to_path_buf());
+        if hir::write_file(path, content)? && !self.written.iter().any(|p| p == path) {
+            self.written.push(path.to_path_buf());
+        }
+        Ok(())
+    }
+}
+
+/// What generating a library did to the files in `cfg.dest`.
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    /// New files, and files whose content changed.
+    pub written: Vec<PathBuf>,
+    /// Generated files that already had this content, or are marked `libninja: static`, so were left alone.
+    pub unchanged: Vec<PathBuf>,
+    /// Files from an earlier run that are no longer generated, and were deleted.
+    pub removed: Vec<PathBuf>,
+}
 
-pub fn generate_rust_library(spec: HirSpec, cfg: Config) -> Result<()> {
+pub fn generate_rust_library(mut spec: HirSpec, cfg: Config) -> Result<Report> {
+    if cfg.split_read_write {
+        spec.split_read_write();
+    }
+    if cfg.widen_numbers {
+        spec.widen_numbers();
+    }
+    spec.override_retries(&cfg.retry, true)?;
+    spec.override_retries(&cfg.no_retry, false)?;
+    spec.retain_string_formats(&cfg.string_formats);
+    spec.add_idempotency_keys(cfg.idempotency_key.as_deref())?;
+    spec.resolve_open_enums(cfg.open_enums);
     let src = cfg.dest.join("src");
     let extras = calculate_extras(&spec);
 
-    let mut m: Modified = HashSet::new();
+    let mut m = Modified::default();
     fs::create_dir_all(&src)?;
 
     write_model_module(&spec, &cfg, &mut m)?;
-    write_request_module(&spec, &cfg, &mut m)?;
+    write_request_module(&spec, &cfg, &extras, &mut m)?;
+    if cfg.example_tests {
+        write_roundtrip_tests(&spec, &cfg, &mut m)?;
+    }
 
     let file = make_lib_rs(&spec, &extras, &cfg);
     write_lib_rs(&src.join("lib.rs"), file, &mut m)?;
 
     write_serde_module(&extras, &src, &mut m)?;
+    if cfg.server {
+        write_server_module(&spec, &cfg, &mut m)?;
+    }
+    if !spec.webhooks.is_empty() {
+        write_webhook_module(&spec, &cfg, &mut m)?;
+    }
 
     // let spec = add_operation_models(opts.language, spec)?;
 
     if cfg.build_examples {
         write_examples_folder(&spec, &cfg, &mut m)?;
     }
-    remove_old_files(&cfg.dest, &m)?;
-    Ok(())
+    if cfg.mock_server {
+        write_mock_server(&spec, &cfg, &mut m)?;
+    }
+    write_manifest(&spec, &cfg, &extras, &mut m)?;
+    let removed = remove_old_files(&cfg.dest, &m)?;
+    let mut unchanged = m
+        .generated
+        .into_iter()
+        .filter(|p| !m.written.contains(p))
+        .collect::<Vec<_>>();
+    unchanged.sort();
+    Ok(Report {
+        written: m.written,
+        unchanged,
+        removed,
+    })
 }
 
 fn write_lib_rs(path: &Path, mut file: File<TokenStream>, m: &mut Modified) -> std::io::Result<()> {
@@ -64,24 +138,28 @@ fn write_lib_rs(path: &Path, mut file: File<TokenStream>, m: &mut Modified) -> s
     write_with_content(path, file, content, m)
 }
 
-fn remove_old_files(dest: &Path, modified: &HashSet<PathBuf>) -> Result<()> {
-    let to_delete = walkdir::WalkDir::new(dest.join("src"))
+/// Delete Rust files that an earlier run generated and this one didn't. Everything in `src`, `examples` and `mock/src`
+/// is generated, but `tests` can have the library's own tests, so only the files libninja writes there are checked.
+fn remove_old_files(dest: &Path, modified: &Modified) -> Result<Vec<PathBuf>> {
+    let to_delete = ["src", "examples", "mock/src"]
         .into_iter()
-        .chain(walkdir::WalkDir::new(dest.join("examples")).into_iter())
+        .flat_map(|dir| walkdir::WalkDir::new(dest.join(dir)))
         .filter_map(|e| e.ok())
         .map(|e| e.into_path())
+        .chain([dest.join("tests").join(ROUNDTRIP_TESTS)].into_iter().filter(|p| p.exists()))
         .filter(|p| p.ext_str() == "rs")
-        .filter(|e| !modified.contains(e))
+        .filter(|e| !modified.generated.contains(e))
         .filter(|p| {
             !fs::read_to_string(&p)
                 .map(|content| content.contains("libninja: static"))
                 .unwrap_or(false)
-        });
-    for e in to_delete {
-        fs::remove_file(&e)?;
-        eprintln!("{}: Remove unused file.", e.display());
+        })
+        .collect::<Vec<_>>();
+    for e in &to_delete {
+        fs::remove_file(e)?;
+        debug!("{}: Remove unused file.", e.display());
     }
-    Ok(())
+    Ok(to_delete)
 }
 
 fn write_rust(path: &Path, code: impl ToRustCode, modified: &mut Modified) -> std::io::Result<()> {
@@ -89,15 +167,39 @@ fn write_rust(path: &Path, code: impl ToRustCode, modified: &mut Modified) -> st
     write_with_content(path, code, content, modified)
 }
 
+/// Render and format a file for each item in parallel, which is most of the time spent on large specs, then write them.
+fn write_rust_par<T: Sync>(
+    items: &[T],
+    render: impl Fn(&T) -> (PathBuf, TokenStream) + Sync,
+    modified: &mut Modified,
+) -> std::io::Result<()> {
+    let files = items
+        .par_iter()
+        .map(|item| {
+            let (path, code) = render(item);
+            (path, format_code(code))
+        })
+        .collect::<Vec<_>>();
+    for (path, code) in files {
+        let content = fs::read_to_string(&path).unwrap_or_default();
+        write_formatted(&path, code, content, modified)?;
+    }
+    Ok(())
+}
+
 fn write_with_content(
     path: &Path,
     code: impl ToRustCode,
-    mut content: String,
+    content: String,
     modified: &mut Modified,
 ) -> std::io::Result<()> {
-    modified.insert(path.to_path_buf());
-    let code = format_code(code.to_rust_code());
+    write_formatted(path, format_code(code.to_rust_code()), content, modified)
+}
+
+/// Write formatted code to the file, keeping what's before `libninja: after`, or all of it with `libninja: static`.
+fn write_formatted(path: &Path, code: String, mut content: String, modified: &mut Modified) -> std::io::Result<()> {
     if content.contains("libninja: static") {
+        modified.generated.insert(path.to_path_buf());
         return Ok(());
     } else if content.contains("libninja: after") {
         let (static_content, _gen) = content.split_once("libninja: after").unwrap();
@@ -107,5 +209,5 @@ fn write_with_content(
     } else {
         content = code;
     }
-    hir::write_file(path, &content)
+    modified.write(path, &content)
 }
@@ -0,0 +1,258 @@
+use anyhow::{anyhow, Result};
+use convert_case::{Case, Casing};
+use hir::{Config, HirSpec, HmacAlgorithm, SignatureEncoding};
+use mir::StringFormat;
+use std::fs;
+use toml_edit::{value, Array, DocumentMut, InlineTable};
+
+use crate::extras::Extras;
+use crate::features::add_cargo_features;
+use crate::Modified;
+
+/// A crate the generated code uses: its name, version, and the features it needs.
+type Dependency = (&'static str, &'static str, Vec<&'static str>);
+
+/// Crates the generated code uses, with the features it needs, which `libninja init` doesn't add. `tokio::time` waits
+/// between retries and times out requests. `Response` has `http`'s status and headers. The rest depend on the spec.
+fn dependencies(spec: &HirSpec, extras: &Extras) -> Vec<Dependency> {
+    let mut dependencies = vec![("http", "1", vec![]), ("tokio", "1", vec!["time"])];
+    let formats = &extras.string_formats;
+    if formats.contains(&StringFormat::Uuid) {
+        dependencies.push(("uuid", "1", vec!["serde"]));
+    }
+    if formats.contains(&StringFormat::Uri) {
+        dependencies.push(("url", "2", vec!["serde"]));
+    }
+    if formats.contains(&StringFormat::Email) {
+        dependencies.push(("email_address", "0.2", vec![]));
+    }
+    // `byte` strings are decoded with base64, and so is the basic auth header encoded.
+    if extras.base64_bytes() || extras.basic_auth {
+        dependencies.push(("base64", "0.22", vec![]));
+    }
+    if extras.regex {
+        dependencies.push(("regex", "1", vec![]));
+    }
+    // Idempotency keys are random UUIDs.
+    if spec.operations.iter().any(|o| o.idempotency_key.is_some()) {
+        match dependencies.iter_mut().find(|(name, ..)| *name == "uuid") {
+            Some((.., features)) => features.push("v4"),
+            None => dependencies.push(("uuid", "1", vec!["v4"])),
+        }
+    }
+    // `verify_webhook_signature` is only in the webhook module, which is only written if there are webhooks.
+    if let Some(signature) = spec.webhook_signature.as_ref().filter(|_| !spec.webhooks.is_empty()) {
+        dependencies.push(("hmac", "0.12", vec![]));
+        dependencies.push(match signature.algorithm {
+            HmacAlgorithm::Sha1 => ("sha1", "0.10", vec![]),
+            HmacAlgorithm::Sha256 | HmacAlgorithm::Sha512 => ("sha2", "0.10", vec![]),
+        });
+        if signature.encoding == SignatureEncoding::Base64 && !dependencies.iter().any(|(name, ..)| *name == "base64") {
+            dependencies.push(("base64", "0.22", vec![]));
+        }
+    }
+    dependencies
+}
+
+/// Update the library's `Cargo.toml` with what the generated code needs, keeping the rest of the file. If there's no
+/// `Cargo.toml` yet, a minimal one is written.
+pub fn write_manifest(spec: &HirSpec, cfg: &Config, extras: &Extras, m: &mut Modified) -> Result<()> {
+    let path = cfg.dest.join("Cargo.toml");
+    let content = fs::read_to_string(&path).unwrap_or_else(|_| {
+        format!(
+            "[package]\nname = \"{}\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+            cfg.package_name().to_case(Case::Kebab)
+        )
+    });
+    let mut manifest = content.parse::<DocumentMut>()?;
+    add_dependencies(&mut manifest, &dependencies(spec, extras)).map_err(|e| anyhow!("{}: {e}", path.display()))?;
+    if cfg.server {
+        add_server_feature(&mut manifest).map_err(|e| anyhow!("{}: {e}", path.display()))?;
+    }
+    if cfg.tag_features {
+        add_cargo_features(&mut manifest, spec, cfg).map_err(|e| anyhow!("{}: {e}", path.display()))?;
+    }
+    m.write(&path, &manifest.to_string())?;
+    Ok(())
+}
+
+/// Add each of `required` that's missing from `[dependencies]`, and the features the code needs to those that aren't.
+/// Versions that are already set are kept.
+fn add_dependencies(manifest: &mut DocumentMut, required: &[Dependency]) -> Result<()> {
+    let dependencies = manifest
+        .entry("dependencies")
+        .or_insert(toml_edit::table())
+        .as_table_like_mut()
+        .ok_or_else(|| anyhow!("`dependencies` isn't a table"))?;
+    for (name, version, features) in required {
+        let Some(dependency) = dependencies.get_mut(name) else {
+            if features.is_empty() {
+                dependencies.insert(name, value(*version));
+            } else {
+                let mut dependency = InlineTable::new();
+                dependency.insert("version", (*version).into());
+                dependency.insert("features", Array::from_iter(features.iter().copied()).into());
+                dependencies.insert(name, value(dependency));
+            }
+            continue;
+        };
+        if features.is_empty() {
+            continue;
+        }
+        // `tokio = "1"` becomes `tokio = { version = "1", features = [...] }`.
+        if let Some(version) = dependency.as_str().map(String::from) {
+            let mut table = InlineTable::new();
+            table.insert("version", version.into());
+            *dependency = value(table);
+        }
+        let table = dependency
+            .as_table_like_mut()
+            .ok_or_else(|| anyhow!("Can't add features to the `{name}` dependency"))?;
+        let enabled = table
+            .entry("features")
+            .or_insert(value(Array::new()))
+            .as_array_mut()
+            .ok_or_else(|| anyhow!("The features of the `{name}` dependency aren't a list"))?;
+        for feature in features {
+            let has = |f: &str| enabled.iter().any(|e| e.as_str() == Some(f));
+            // `full` enables every tokio feature.
+            if !has(feature) && !has("full") {
+                enabled.push(*feature);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `src/server.rs` is behind a `server` feature, which enables an optional `axum` dependency. A `server` feature that's
+/// already there is kept. If `axum` is already a dependency that isn't optional, the feature enables nothing.
+fn add_server_feature(manifest: &mut DocumentMut) -> Result<()> {
+    let dependencies = manifest
+        .entry("dependencies")
+        .or_insert(toml_edit::table())
+        .as_table_like_mut()
+        .ok_or_else(|| anyhow!("`dependencies` isn't a table"))?;
+    let optional = match dependencies.get("axum") {
+        Some(axum) => axum.get("optional").and_then(|o| o.as_bool()).unwrap_or(false),
+        None => {
+            let mut axum = InlineTable::new();
+            axum.insert("version", "0.8".into());
+            axum.insert("optional", true.into());
+            dependencies.insert("axum", value(axum));
+            true
+        }
+    };
+    let features = manifest
+        .entry("features")
+        .or_insert(toml_edit::table())
+        .as_table_like_mut()
+        .ok_or_else(|| anyhow!("`features` isn't a table"))?;
+    if !features.contains_key("server") {
+        let enables = if optional {
+            Array::from_iter(["dep:axum"])
+        } else {
+            Array::new()
+        };
+        features.insert("server", value(enables));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extras::calculate_extras;
+    use hir::{Constraints, Operation, Parameter, Webhook, WebhookSignature};
+    use mir::Ty;
+
+    fn add(manifest: &str) -> String {
+        let mut manifest = manifest.parse::<DocumentMut>().unwrap();
+        let required = [("http", "1", vec![]), ("tokio", "1", vec!["time"])];
+        add_dependencies(&mut manifest, &required).unwrap();
+        manifest.to_string()
+    }
+
+    #[test]
+    fn test_add_dependencies() {
+        let added = add("[package]\nname = \"pay\"\n\n[dependencies]\nhttpclient = \"0.23\"\n");
+        assert!(added
+            .ends_with("httpclient = \"0.23\"\nhttp = \"1\"\ntokio = { version = \"1\", features = [\"time\"] }\n"));
+        let added = add("[dependencies]\nhttp = \"1.1\"\ntokio = \"1.38\"\n");
+        assert_eq!(
+            added,
+            "[dependencies]\nhttp = \"1.1\"\ntokio = { version = \"1.38\", features = [\"time\"] }\n"
+        );
+        let added = add("[dependencies]\nhttp = \"1\"\ntokio = { version = \"1\", features = [\"macros\"] }\n");
+        assert_eq!(
+            added,
+            "[dependencies]\nhttp = \"1\"\ntokio = { version = \"1\", features = [\"macros\", \"time\"] }\n"
+        );
+        let full = "[dependencies]\nhttp = \"1\"\n\n[dependencies.tokio]\nversion = \"1\"\nfeatures = [\"full\"]\n";
+        assert_eq!(add(full), full);
+    }
+
+    #[test]
+    fn test_add_server_feature() {
+        let mut manifest = "[package]\nname = \"pay\"\n".parse::<DocumentMut>().unwrap();
+        add_server_feature(&mut manifest).unwrap();
+        let manifest = manifest.to_string();
+        assert!(manifest.contains("axum = { version = \"0.8\", optional = true }\n"));
+        assert!(manifest.contains("[features]\nserver = [\"dep:axum\"]\n"));
+
+        let mut manifest = "[dependencies]\naxum = \"0.8\"\n".parse::<DocumentMut>().unwrap();
+        add_server_feature(&mut manifest).unwrap();
+        assert_eq!(
+            manifest.to_string(),
+            "[dependencies]\naxum = \"0.8\"\n\n[features]\nserver = []\n"
+        );
+    }
+
+    #[test]
+    fn test_dependencies_from_spec() {
+        let mut spec = HirSpec::default();
+        spec.operations.push(Operation {
+            name: "getUser".to_string(),
+            parameters: vec![
+                Parameter::path("id", Ty::FormattedString(StringFormat::Uuid)),
+                Parameter::path("email", Ty::FormattedString(StringFormat::Email)),
+                Parameter {
+                    constraints: Constraints {
+                        pattern: Some("^[a-z]+$".to_string()),
+                        ..Constraints::default()
+                    },
+                    ..Parameter::path("name", Ty::String)
+                },
+            ],
+            ..Operation::default()
+        });
+        let required = dependencies(&spec, &calculate_extras(&spec));
+        let names = required.iter().map(|(name, ..)| *name).collect::<Vec<_>>();
+        assert_eq!(names, ["http", "tokio", "uuid", "email_address", "regex"]);
+        assert_eq!(required[2], ("uuid", "1", vec!["serde"]));
+
+        spec.operations[0].idempotency_key = Some("Idempotency-Key".to_string());
+        let required = dependencies(&spec, &calculate_extras(&spec));
+        assert_eq!(required[2], ("uuid", "1", vec!["serde", "v4"]));
+    }
+
+    #[test]
+    fn test_webhook_signature_dependencies() {
+        let mut spec = HirSpec::default();
+        spec.webhooks.push(Webhook {
+            name: "invoicePaid".to_string(),
+            event: "invoice.paid".to_string(),
+            doc: None,
+            body: Ty::Any(None),
+            deprecated: false,
+        });
+        spec.webhook_signature = Some(WebhookSignature {
+            header: "X-Signature".to_string(),
+            algorithm: HmacAlgorithm::Sha1,
+            encoding: SignatureEncoding::Base64,
+            prefix: String::new(),
+        });
+        let required = dependencies(&spec, &calculate_extras(&spec));
+        let names = required.iter().map(|(name, ..)| *name).collect::<Vec<_>>();
+        assert_eq!(names, ["http", "tokio", "hmac", "sha1", "base64"]);
+    }
+}
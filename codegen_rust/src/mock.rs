@@ -0,0 +1,298 @@
+use std::collections::BTreeMap;
+
+use convert_case::{Case, Casing};
+use hir::{Config, HirField, HirSpec, Record, Struct};
+use mir::{DateSerialization, IntegerSerialization, StringFormat, Ty};
+use mir_rust::ToRustIdent;
+use proc_macro2::TokenStream;
+use quote::quote;
+use serde_json::{json, Value};
+
+use crate::{write_rust, Modified};
+
+/// Write an axum server crate to `{dest}/mock`, which answers every operation with example data from the
+/// spec. `Cargo.toml` is only written if it doesn't exist yet, so it can be edited.
+pub fn write_mock_server(spec: &HirSpec, cfg: &Config, modified: &mut Modified) -> std::io::Result<()> {
+    let dest = cfg.dest.join("mock");
+    std::fs::create_dir_all(dest.join("src"))?;
+    let manifest = dest.join("Cargo.toml");
+    if !manifest.exists() {
+        modified.write(&manifest, &mock_manifest(cfg))?;
+    }
+    write_rust(&dest.join("src").join("lib.rs"), make_mock_server(spec, cfg), modified)
+}
+
+fn mock_manifest(cfg: &Config) -> String {
+    format!(
+        r#"[package]
+name = "{}-mock"
+version = "0.1.0"
+edition = "2021"
+
+[dependencies]
+axum = "0.8"
+serde = "1"
+serde_json = "1"
+tokio = {{ version = "1", features = ["net", "rt"] }}
+"#,
+        cfg.package_name().to_case(Case::Kebab)
+    )
+}
+
+pub fn make_mock_server(spec: &HirSpec, cfg: &Config) -> TokenStream {
+    let doc = format!(
+        " A mock of the {} API. Every operation responds with example data from the API spec, unless a test \
+        overrides it with [`MockServer::on`] or [`MockServer::respond`].",
+        cfg.name
+    );
+    let operation_names = spec.operations.iter().map(|o| o.file_name());
+
+    let mut paths: BTreeMap<&str, Vec<TokenStream>> = BTreeMap::new();
+    for operation in &spec.operations {
+        let name = operation.file_name();
+        let example = serde_json::to_string(&example_json(&operation.ret, spec, &mut Vec::new())).unwrap();
+        let method = operation.method.to_lowercase().to_rust_ident();
+        paths
+            .entry(&operation.path)
+            .or_default()
+            .push(quote! { #method(handler(&handlers, #name, #example)) });
+    }
+    let routes = paths.into_iter().map(|(path, methods)| {
+        let mut methods = methods.into_iter();
+        let first = methods.next().unwrap();
+        let rest = methods.map(|m| quote! { .#m });
+        quote! {
+            .route(#path, axum::routing::#first #(#rest)*)
+        }
+    });
+
+    quote! {
+        #![doc = #doc]
+        use std::collections::HashMap;
+        use std::future::{ready, Ready};
+        use std::sync::Arc;
+
+        use axum::extract::Request;
+        use axum::http::{header, StatusCode};
+        use axum::response::{IntoResponse, Response};
+        use axum::Router;
+
+        type Handler = Arc<dyn Fn(Request) -> Response + Send + Sync>;
+
+        /// Operation names, as accepted by [`MockServer::on`].
+        pub const OPERATIONS: &[&str] = &[#(#operation_names),*];
+
+        #[derive(Clone, Default)]
+        pub struct MockServer {
+            handlers: HashMap<&'static str, Handler>,
+        }
+
+        impl MockServer {
+            pub fn new() -> Self {
+                Self::default()
+            }
+
+            /// Handle `operation` (its snake_case name, e.g. `"list_users"`) with `handler` instead of the example response.
+            pub fn on(
+                mut self,
+                operation: &'static str,
+                handler: impl Fn(Request) -> Response + Send + Sync + 'static,
+            ) -> Self {
+                assert!(OPERATIONS.contains(&operation), "Unknown operation: {operation}");
+                self.handlers.insert(operation, Arc::new(handler));
+                self
+            }
+
+            /// Respond to `operation` with `body` as JSON.
+            pub fn respond(self, operation: &'static str, body: impl serde::Serialize) -> Self {
+                let body = serde_json::to_string(&body).expect("Failed to serialize mock response");
+                self.on(operation, move |_| json_response(body.clone()))
+            }
+
+            pub fn router(self) -> Router {
+                let handlers = Arc::new(self.handlers);
+                Router::new()
+                    #(#routes)*
+                    .fallback(|| async { StatusCode::NOT_FOUND })
+            }
+
+            /// Serve on a free local port in the background, and return the base URL to point the client at.
+            pub async fn start(self) -> String {
+                let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+                    .await
+                    .expect("Failed to bind mock server");
+                let addr = listener.local_addr().unwrap();
+                let router = self.router();
+                tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+                format!("http://{addr}")
+            }
+        }
+
+        fn handler(
+            handlers: &Arc<HashMap<&'static str, Handler>>,
+            operation: &'static str,
+            example: &'static str,
+        ) -> impl FnOnce(Request) -> Ready<Response> + Clone + Send + Sync + 'static {
+            let handlers = handlers.clone();
+            move |request| {
+                ready(match handlers.get(operation) {
+                    Some(handler) => handler(request),
+                    None => json_response(example.to_string()),
+                })
+            }
+        }
+
+        fn json_response(body: String) -> Response {
+            ([(header::CONTENT_TYPE, "application/json")], body).into_response()
+        }
+    }
+}
+
+/// A JSON value of type `ty`, using examples from the spec where there are any. `path` holds the models
+/// being built, so recursive models stop at the first repeat.
+pub fn example_json(ty: &Ty, spec: &HirSpec, path: &mut Vec<String>) -> Value {
+    match ty {
+        Ty::String => json!("string"),
+        Ty::FormattedString(format) => match format {
+            StringFormat::Uuid => json!("00000000-0000-0000-0000-000000000000"),
+            StringFormat::Uri => json!("https://example.com"),
+            StringFormat::Email => json!("user@example.com"),
+            StringFormat::Ip => json!("127.0.0.1"),
+            StringFormat::Byte | StringFormat::Binary => json!(""),
+            StringFormat::Duration => json!("PT1S"),
+        },
+        Ty::Integer {
+            ser: IntegerSerialization::String,
+            ..
+        } => json!("1"),
+        Ty::Integer { .. } => json!(1),
+        Ty::Float { .. } => json!(1.0),
+        Ty::Boolean => json!(true),
+        Ty::Array(inner) => match inner.inner_model() {
+            Some(model) if path.contains(model) => json!([]),
+            _ => json!([example_json(inner, spec, path)]),
+        },
+        Ty::HashMap(inner) => match inner.inner_model() {
+            Some(model) if path.contains(model) => json!({}),
+            _ => json!({ "key": example_json(inner, spec, path) }),
+        },
+        Ty::Model(name) if path.contains(name) => Value::Null,
+        Ty::Model(name) => {
+            let Ok(record) = spec.get_record(name) else {
+                return Value::Null;
+            };
+            if let Record::Struct(Struct {
+                example: Some(example), ..
+            }) = record
+            {
+                return example.clone();
+            }
+            path.push(name.clone());
+            let value = match record {
+                Record::Struct(s) => {
+                    let mut object = serde_json::Map::new();
+                    for (name, field) in &s.fields {
+                        if field.write_only
+                            || (field.optional && field.ty.inner_model().is_some_and(|m| path.contains(m)))
+                        {
+                            continue;
+                        }
+                        let value = field_example(field, spec, path);
+                        match value {
+                            Value::Object(fields) if field.flatten => object.extend(fields),
+                            _ if field.flatten => {}
+                            value => {
+                                object.insert(name.clone(), value);
+                            }
+                        }
+                    }
+                    Value::Object(object)
+                }
+                Record::Enum(e) => match e.variants.first() {
+                    Some(v) => match v.discriminant {
+                        Some(n) => json!(n),
+                        None => json!(v.value),
+                    },
+                    None => Value::Null,
+                },
+                Record::NewType(nt) => nt
+                    .fields
+                    .first()
+                    .map(|f| field_example(f, spec, path))
+                    .unwrap_or(Value::Null),
+                Record::TypeAlias(_, field) => field_example(field, spec, path),
+            };
+            path.pop();
+            value
+        }
+        Ty::Unit => Value::Null,
+        Ty::Any(_) => json!({}),
+        Ty::Date {
+            ser: DateSerialization::Integer,
+        } => json!(20240101),
+        Ty::Date { .. } => json!("2024-01-01"),
+        Ty::DateTime => json!("2024-01-01T00:00:00Z"),
+        Ty::Currency { .. } => json!("100.01"),
+    }
+}
+
+/// The field's `example` from the spec, or one made up from its type and constraints.
+fn field_example(field: &HirField, spec: &HirSpec, path: &mut Vec<String>) -> Value {
+    if let Some(example) = &field.example {
+        return example.clone();
+    }
+    let c = &field.constraints;
+    match example_json(&field.ty, spec, path) {
+        Value::String(mut s) if matches!(field.ty, Ty::String) => {
+            if let Some(min) = c.min_length {
+                while s.chars().count() < min {
+                    s.push('a');
+                }
+            }
+            if let Some(max) = c.max_length {
+                s = s.chars().take(max).collect();
+            }
+            Value::String(s)
+        }
+        Value::Number(_) if c.minimum.is_some() || c.maximum.is_some() => {
+            let mut n = c.minimum.unwrap_or(c.maximum.unwrap_or(1.0));
+            if c.minimum.is_some() && c.exclusive_minimum {
+                n += 1.0;
+            } else if c.minimum.is_none() && c.exclusive_maximum {
+                n -= 1.0;
+            }
+            match field.ty {
+                Ty::Integer { .. } => json!(n as i64),
+                _ => json!(n),
+            }
+        }
+        value => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_example_json() {
+        let mut spec = HirSpec::default();
+        let mut id = HirField::new(Ty::String);
+        id.constraints.min_length = Some(8);
+        let mut count = HirField::new(Ty::integer());
+        count.constraints.minimum = Some(5.0);
+        let mut name = HirField::new(Ty::String);
+        name.example = Some(json!("Ada"));
+        let parent = HirField::new(Ty::model("User")).nullable();
+        spec.insert_schema(Struct {
+            name: "User".to_string(),
+            fields: [("id", id), ("count", count), ("name", name), ("parent", parent)]
+                .into_iter()
+                .map(|(n, f)| (n.to_string(), f))
+                .collect(),
+            ..Struct::default()
+        });
+        let value = example_json(&Ty::Array(Box::new(Ty::model("User"))), &spec, &mut Vec::new());
+        assert_eq!(value, json!([{"id": "stringaa", "count": 5, "name": "Ada"}]));
+    }
+}
@@ -1,4 +1,5 @@
-use crate::{write_rust, Modified};
+use crate::features::ModelFeatures;
+use crate::{write_rust, write_rust_par, Modified};
 use hir::{Config, HirSpec, Record};
 use mir::{import, File, Ident, Import, Item};
 use mir_rust::{make_item, sanitize_filename, ToRustCode, ToRustIdent};
@@ -15,34 +16,42 @@ pub fn write_model_module(spec: &HirSpec, cfg: &Config, m: &mut Modified) -> any
     let path = src.join("model").join("mod.rs");
     write_rust(&path, model_rs.to_rust_code(), m)?;
 
-    for (name, record) in &spec.schemas {
-        let file = make_single_module(record, spec, cfg);
-        let name = sanitize_filename(name);
-        let dest = src.join("model").join(&name).with_extension("rs");
-        write_rust(&dest, file.to_rust_code(), m)?;
-    }
+    let records = spec.schemas.iter().collect::<Vec<_>>();
+    write_rust_par(
+        &records,
+        |(name, record)| {
+            let file = make_single_module(record, spec, cfg);
+            let dest = src.join("model").join(sanitize_filename(name)).with_extension("rs");
+            (dest, file.to_rust_code())
+        },
+        m,
+    )?;
     Ok(())
 }
 
 /// Generate a model.rs file that just imports from dependents.
-pub fn make_model_rs(spec: &HirSpec, _cfg: &Config) -> File<TokenStream> {
-    let it = spec.schemas.keys();
-    let imports = it
-        .clone()
-        .map(|name: &String| {
-            let fname = sanitize_filename(&name);
-            Import::new(&fname, vec!["*"]).public()
-        })
-        .collect();
-    let items = it
-        .clone()
-        .map(|name| {
-            let name = Ident(sanitize_filename(name));
-            Item::Block(quote! {
-                mod #name;
-            })
-        })
-        .collect();
+pub fn make_model_rs(spec: &HirSpec, cfg: &Config) -> File<TokenStream> {
+    let features = ModelFeatures::new(spec, cfg);
+    let mut imports = Vec::new();
+    let mut items = Vec::new();
+    for name in spec.schemas.keys() {
+        let fname = sanitize_filename(name);
+        let feature = features.cfg(name);
+        let module = Ident(fname.clone());
+        if feature.is_empty() {
+            imports.push(Import::new(&fname, vec!["*"]).public());
+            items.push(Item::Block(quote! {
+                mod #module;
+            }));
+        } else {
+            items.push(Item::Block(quote! {
+                #feature
+                mod #module;
+                #feature
+                pub use #module::*;
+            }));
+        }
+    }
     File {
         imports,
         items,
@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::default::Default;
 use std::fs;
 use std::sync::OnceLock;
@@ -7,39 +8,81 @@ use proc_macro2::TokenStream;
 use quote::quote;
 use regex::Captures;
 
-use hir::{Config, HirSpec, Language, Location, Operation, Parameter};
+use hir::{Config, HirField, HirSpec, Language, Location, Operation, Parameter};
 use mir::{import, Arg, Class, Doc, Field, File, Function, Ident, Import, Item, Ty, Visibility};
 
-use mir_rust::{derives_to_tokens, ToRustCode, ToRustIdent, ToRustType};
+use mir_rust::{deprecated_to_tokens, derives_to_tokens, make_validate_method, ToRustCode, ToRustIdent, ToRustType};
 
-use crate::{client::build_api_client_method, write_rust, Modified};
+use crate::client::{build_api_client_method, tag_client_name, tag_module};
+use crate::extras::Extras;
+use crate::features::operation_cfg;
+use crate::{write_rust, write_rust_par, Modified};
 use std::io::Result;
 
-pub fn write_request_module(spec: &HirSpec, cfg: &Config, m: &mut Modified) -> Result<()> {
+pub fn write_request_module(spec: &HirSpec, cfg: &Config, extras: &Extras, m: &mut Modified) -> Result<()> {
     let src = cfg.src();
     let imports = vec![];
     fs::create_dir_all(src.join("request"))?;
-    let mut modules: Vec<(Ident, Ident)> = vec![];
+    let mut modules: Vec<(Ident, Ident, TokenStream)> = vec![];
+    let mut tags: BTreeMap<String, (TokenStream, Vec<(Ident, Ident)>)> = BTreeMap::new();
 
+    let mut files = vec![];
     for operation in &spec.operations {
-        modules.push((
+        let module = (
             Ident(operation.file_name()),
             operation.request_struct_name().to_rust_struct(),
-        ));
-        let file = make_single_module(operation, &spec, cfg);
+        );
         let fname = operation.file_name();
-        let path = src.join("request").join(&fname).with_extension("rs");
-        write_rust(&path, file, m)?;
+        let dir = match tag_module(operation, cfg) {
+            Some(tag) => {
+                let (_, modules) = tags
+                    .entry(tag.clone())
+                    .or_insert_with(|| (operation_cfg(operation, cfg), vec![]));
+                modules.push(module);
+                src.join("request").join(tag)
+            }
+            None => {
+                modules.push((module.0, module.1, operation_cfg(operation, cfg)));
+                src.join("request")
+            }
+        };
+        fs::create_dir_all(&dir)?;
+        files.push((operation, dir.join(&fname).with_extension("rs")));
     }
-    let items = modules
+    write_rust_par(
+        &files,
+        |(operation, path)| {
+            (
+                path.clone(),
+                make_single_module(operation, spec, cfg, extras).to_rust_code(),
+            )
+        },
+        m,
+    )?;
+    let mut items: Vec<Item<TokenStream>> = modules
         .into_iter()
-        .map(|(m, s)| {
+        .map(|(m, s, feature)| {
             Item::Block(quote! {
+                #feature
                 pub mod #m;
+                #feature
                 pub use #m::#s;
             })
         })
         .collect();
+    for (tag, (feature, modules)) in &tags {
+        let file = make_tag_module(tag, modules, cfg);
+        write_rust(&src.join("request").join(tag).join("mod.rs"), file, m)?;
+        let tag_client = tag_client_name(tag);
+        let tag = Ident(tag.clone());
+        let structs = modules.iter().map(|(_, s)| s);
+        items.push(Item::Block(quote! {
+            #feature
+            pub mod #tag;
+            #feature
+            pub use #tag::{#tag_client, #(#structs),*};
+        }));
+    }
     let file = File {
         imports,
         items,
@@ -48,7 +91,42 @@ pub fn write_request_module(spec: &HirSpec, cfg: &Config, m: &mut Modified) -> R
     write_rust(&src.join("request").join("mod.rs"), file, m)
 }
 
-pub fn make_single_module(operation: &Operation, spec: &HirSpec, cfg: &Config) -> File<TokenStream> {
+/// `request/{tag}/mod.rs`, with the tag's request modules and its sub-client.
+fn make_tag_module(tag: &str, modules: &[(Ident, Ident)], cfg: &Config) -> File<TokenStream> {
+    let client_name = cfg.client_name();
+    let tag_client = tag_client_name(tag);
+    let accessor = tag.to_rust_ident();
+    let doc = format!(" Operations tagged `{tag}`.");
+    let mut items: Vec<Item<TokenStream>> = modules
+        .iter()
+        .map(|(m, s)| {
+            Item::Block(quote! {
+                pub mod #m;
+                pub use #m::#s;
+            })
+        })
+        .collect();
+    items.push(Item::Block(quote! {
+        #[doc = #doc]
+        #[derive(Clone, Copy)]
+        pub struct #tag_client<'a> {
+            client: &'a crate::#client_name,
+        }
+
+        impl crate::#client_name {
+            #[doc = #doc]
+            pub fn #accessor(&self) -> #tag_client<'_> {
+                #tag_client { client: self }
+            }
+        }
+    }));
+    File {
+        items,
+        ..File::default()
+    }
+}
+
+pub fn make_single_module(operation: &Operation, spec: &HirSpec, cfg: &Config, extras: &Extras) -> File<TokenStream> {
     let client_name = cfg.client_name();
     let authenticate = spec
         .has_security()
@@ -60,7 +138,7 @@ pub fn make_single_module(operation: &Operation, spec: &HirSpec, cfg: &Config) -
         .unwrap_or_default();
 
     let mut imports: Vec<Import> = vec![
-        import!(crate, FluentRequest),
+        import!(crate, FluentRequest, RequestOptions),
         import!(serde, Serialize, Deserialize),
         import!(httpclient, InMemoryResponseExt),
     ];
@@ -74,37 +152,94 @@ pub fn make_single_module(operation: &Operation, spec: &HirSpec, cfg: &Config) -
         .map(|s| s.to_rust_code());
 
     let assign_inputs = assign_inputs_to_request(&operation.parameters);
-    let output = if operation.ret.is_primitive() {
-        quote! { #response }
+    let validate = if validate_method(operation, spec).is_some() && !cfg.skip_validation {
+        quote! {
+            self.params.validate()?;
+        }
+    } else {
+        TokenStream::new()
+    };
+    // `x-retry` and `--retry` decide whether the operation is safe to retry, over its method.
+    let idempotent = operation.retry.enabled.unwrap_or_else(|| operation.is_idempotent());
+    let max_retries = match (operation.retry.enabled, operation.retry.max_retries) {
+        (Some(false), _) => quote!(Some(0)),
+        (_, Some(n)) => quote!(Some(#n)),
+        _ => quote!(None),
+    };
+    // Set once, so every retry sends the same key.
+    let (mut_self, idempotency_key) = match &operation.idempotency_key {
+        Some(header) => {
+            let field = header.to_rust_ident();
+            (
+                quote!(mut),
+                quote! {
+                    self.params.#field.get_or_insert_with(|| uuid::Uuid::new_v4().to_string());
+                },
+            )
+        }
+        None => (TokenStream::new(), TokenStream::new()),
+    };
+    let output = match &operation.ret {
+        Ty::Model(_) => quote! { crate::model::#response },
+        ret => {
+            if let Some(model) = ret.inner_model() {
+                add_model_import(&mut imports, model);
+            }
+            quote! { #response }
+        }
+    };
+
+    let header_accessors = response_header_accessors(operation);
+    let error = if extras.validates_requests(cfg) {
+        quote!(crate::RequestError)
     } else {
-        quote! { crate::model::#response }
+        quote!(httpclient::InMemoryError)
     };
 
     let impl_block = quote! {
         impl FluentRequest<'_, #struct_name> {
             #(#builder_methods)*
+
+            /// Send the request, and return the response's status and headers along with the parsed body.
+            pub async fn send_with_response(#mut_self self) -> Result<crate::Response<#output, #struct_name>, #error> {
+                #validate
+                #idempotency_key
+                let res = self.client.retry.send(#idempotent, #max_retries, || {
+                    let url = #url;
+                    let url = self.options.url(url);
+                    let mut r = self.client.client.#method(&url);
+                    #assign_inputs
+                    #authenticate
+                    self.options.send(r)
+                }).await?;
+                let status = res.status();
+                let headers = res.headers().clone();
+                let body = res.json()?;
+                Ok(crate::Response::new(status, headers, body))
+            }
         }
         impl<'a> ::std::future::IntoFuture for FluentRequest<'a, #struct_name> {
-            type Output = httpclient::InMemoryResult<#output>;
+            type Output = Result<#output, #error>;
             type IntoFuture = ::futures::future::BoxFuture<'a, Self::Output>;
 
             fn into_future(self) -> Self::IntoFuture {
-                Box::pin(async move {
-                    let url = #url;
-                    let mut r = self.client.client.#method(url);
-                    #assign_inputs
-                    #authenticate
-                    let res = r.await?;
-                    res.json().map_err(Into::into)
-                })
+                Box::pin(async move { Ok(self.send_with_response().await?.body) })
             }
         }
+        #header_accessors
     };
     let mut items: Vec<Item<TokenStream>> = request_structs.into_iter().map(|s| Item::Class(s)).collect();
     items.push(Item::Block(impl_block));
-    let client_method = build_api_client_method(operation);
+    let client_method = build_api_client_method(operation, spec, cfg);
+    let client_impl = match tag_module(operation, cfg) {
+        Some(tag) => {
+            let tag_client = tag_client_name(&tag);
+            quote!(impl<'a> super::#tag_client<'a>)
+        }
+        None => quote!(impl crate::#client_name),
+    };
     items.push(Item::Block(quote! {
-        impl crate::#client_name {
+        #client_impl {
             #client_method
         }
     }));
@@ -117,6 +252,48 @@ pub fn make_single_module(operation: &Operation, spec: &HirSpec, cfg: &Config) -
     }
 }
 
+/// Methods on the operation's `Response` to read the headers the spec declares, parsed into their type.
+fn response_header_accessors(operation: &Operation) -> TokenStream {
+    if operation.response_headers.is_empty() {
+        return TokenStream::new();
+    }
+    let struct_name = operation.request_struct_name().to_rust_struct();
+    let accessors = operation.response_headers.iter().map(|h| {
+        let key = &h.name;
+        let name = h.name.to_rust_ident();
+        let doc = h.doc.clone().to_rust_code();
+        let value = quote!(self.headers.get(#key)?.to_str().ok());
+        match &h.ty {
+            Ty::Integer { .. }
+            | Ty::Float { .. }
+            | Ty::Boolean
+            | Ty::Date { .. }
+            | Ty::DateTime
+            | Ty::Currency { .. }
+            | Ty::FormattedString(_) => {
+                let ty = h.ty.to_rust_type();
+                quote! {
+                    #doc
+                    pub fn #name(&self) -> Option<#ty> {
+                        #value?.parse().ok()
+                    }
+                }
+            }
+            _ => quote! {
+                #doc
+                pub fn #name(&self) -> Option<&str> {
+                    #value
+                }
+            },
+        }
+    });
+    quote! {
+        impl<T> crate::Response<T, #struct_name> {
+            #(#accessors)*
+        }
+    }
+}
+
 pub fn assign_inputs_to_request(inputs: &[Parameter]) -> TokenStream {
     let params_except_path: Vec<&Parameter> = inputs
         .iter()
@@ -127,7 +304,7 @@ pub fn assign_inputs_to_request(inputs: &[Parameter]) -> TokenStream {
         .all(|&input| input.location == Location::Query)
     {
         return quote! {
-            r = r.set_query(self.params);
+            r = r.set_query(&self.params);
         };
     }
     let assigns = inputs
@@ -167,7 +344,7 @@ pub fn assign_inputs_to_request(inputs: &[Parameter]) -> TokenStream {
                 let container = if input.optional {
                     quote! { unwrapped }
                 } else {
-                    quote! { self.params.#field }
+                    quote! { &self.params.#field }
                 };
                 assign = quote! {
                     for item in #container {
@@ -265,6 +442,20 @@ pub fn make_struct_fields(
         .collect()
 }
 
+/// Methods every `FluentRequest` has, besides the builder methods for its parameters.
+const REQUEST_OPTION_METHODS: [&str; 5] = ["timeout", "header", "base_url", "query_extra", "send_with_response"];
+
+/// The builder method for an optional parameter, which is suffixed with `_param` if another `FluentRequest` method
+/// has the same name.
+pub fn builder_method_name(param: &Parameter) -> Ident {
+    let name = param.name.to_rust_ident();
+    if REQUEST_OPTION_METHODS.contains(&name.0.as_str()) {
+        Ident(format!("{}_param", name.0))
+    } else {
+        name
+    }
+}
+
 /// Build the various "builder" methods for optional parameters for a request struct
 pub fn build_request_struct_builder_methods(operation: &Operation) -> Vec<Function<TokenStream>> {
     operation
@@ -293,10 +484,13 @@ pub fn build_request_struct_builder_methods(operation: &Operation) -> Vec<Functi
                     self
                 };
             }
-            let name: Ident = a.name.to_rust_ident();
+            let mut doc = format!("Set the value of the {} field.", name.0);
+            if let Some(default) = &a.default {
+                doc.push_str(&format!("\n\nIf not set, the server uses `{}`.", default));
+            }
             Function {
-                doc: Some(Doc(format!("Set the value of the {} field.", name.0))),
-                name,
+                doc: Some(Doc(doc)),
+                name: builder_method_name(a),
                 args: vec![
                     Arg::SelfArg {
                         mutable: true,
@@ -311,15 +505,26 @@ pub fn build_request_struct_builder_methods(operation: &Operation) -> Vec<Functi
                 ret: quote! {Self},
                 body,
                 vis: Visibility::Public,
+                attributes: vec![deprecated_to_tokens(a.deprecated)],
                 ..Function::default()
             }
         })
         .collect()
 }
 
+/// The request struct's `validate()` method, or `None` if none of its parameters have constraints to check.
+fn validate_method(operation: &Operation, spec: &HirSpec) -> Option<Function<TokenStream>> {
+    let fields = operation
+        .parameters
+        .iter()
+        .map(|p| (p.name.clone(), HirField::from(p)))
+        .collect::<Vec<_>>();
+    make_validate_method(&fields, spec)
+}
+
 pub fn build_request_struct(
     operation: &Operation,
-    _spec: &HirSpec,
+    spec: &HirSpec,
     opt: &Config,
     imports: &mut Vec<Import>,
 ) -> Vec<Class<TokenStream>> {
@@ -335,10 +540,13 @@ pub fn build_request_struct(
 On request success, this will return a [`{response}`]."#,
     )));
 
+    let methods = validate_method(operation, spec).into_iter().collect();
+
     let mut result = vec![Class {
         name: operation.request_struct_name().to_rust_struct(),
         doc,
         fields: instance_fields,
+        methods,
         lifetimes: vec![],
         vis: Visibility::Public,
         attributes: vec![quote! {#[derive(Debug, Clone, Serialize, Deserialize #derives)]}],
@@ -391,3 +599,140 @@ On request success, this will return a [`{response}`]."#,
 //         #(#request_structs)*
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    use hir::{Constraints, Location};
+    use mir_rust::format_code;
+
+    use super::*;
+    use crate::extras::calculate_extras;
+
+    fn render(operation: &Operation, cfg: &Config) -> String {
+        let mut spec = HirSpec::default();
+        spec.operations.push(operation.clone());
+        let extras = calculate_extras(&spec);
+        format_code(make_single_module(operation, &spec, cfg, &extras).to_rust_code())
+    }
+
+    fn query(name: &str, ty: Ty) -> Parameter {
+        Parameter {
+            location: Location::Query,
+            optional: true,
+            ..Parameter::path(name, ty)
+        }
+    }
+
+    fn list_users() -> Operation {
+        Operation {
+            name: "listUsers".to_string(),
+            path: "/users".to_string(),
+            method: "get".to_string(),
+            parameters: vec![query("timeout", Ty::Boolean), query("limit", Ty::Boolean)],
+            ..Operation::default()
+        }
+    }
+
+    #[test]
+    fn test_param_named_like_request_option() {
+        let operation = list_users();
+        let names = build_request_struct_builder_methods(&operation)
+            .into_iter()
+            .map(|f| f.name.0)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["timeout_param", "limit"]);
+
+        let code = render(&operation, &Config::default());
+        assert!(code.contains("pub fn timeout_param(mut self, timeout: bool) -> Self {"));
+        assert!(code.contains("self.params.timeout = Some(timeout);"));
+        assert!(code.contains("pub timeout: Option<bool>,"));
+    }
+
+    #[test]
+    fn test_request_options_applied() {
+        let code = render(&list_users(), &Config::default());
+        assert!(code.contains("let url = self.options.url(url);"));
+        assert!(code.contains("self.options.send(r)"));
+        assert!(!code.contains("validate()"));
+    }
+
+    #[test]
+    fn test_validation_error() {
+        let mut operation = list_users();
+        operation.parameters[1].constraints = Constraints {
+            maximum: Some(100.0),
+            ..Constraints::default()
+        };
+        operation.parameters[1].ty = Ty::Float {
+            size: Default::default(),
+        };
+        let code = render(&operation, &Config::default());
+        assert!(code.contains("self.params.validate()?;"));
+        assert!(code.contains("Result<crate::Response<(), ListUsersRequest>, crate::RequestError>"));
+        assert!(code.contains("type Output = Result<(), crate::RequestError>;"));
+
+        let cfg = Config {
+            skip_validation: true,
+            ..Config::default()
+        };
+        let code = render(&operation, &cfg);
+        assert!(!code.contains("validate()?"));
+        assert!(code.contains("type Output = Result<(), httpclient::InMemoryError>;"));
+    }
+
+    #[test]
+    fn test_existing_idempotency_key_header() {
+        let mut spec = HirSpec::default();
+        spec.operations.push(Operation {
+            name: "createRefund".to_string(),
+            path: "/refunds".to_string(),
+            method: "post".to_string(),
+            parameters: vec![Parameter {
+                location: Location::Header,
+                optional: true,
+                ..Parameter::path("Idempotency-Key", Ty::String)
+            }],
+            ..Operation::default()
+        });
+        spec.add_idempotency_keys(Some("idempotency-key")).unwrap();
+        let code = render(&spec.operations[0], &Config::default());
+        assert!(code.contains("pub idempotency_key: Option<String>,"));
+        assert!(code.contains("pub async fn send_with_response(\n        mut self,"));
+        assert!(code.contains(
+            "self.params\n            .idempotency_key\n            .get_or_insert_with(|| uuid::Uuid::new_v4().to_string());"
+        ));
+    }
+
+    #[test]
+    fn test_tag_module_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut spec = HirSpec::default();
+        for (name, tag) in [("listTypes", "Type"), ("listInvoices", "Billing & Payments")] {
+            spec.operations.push(Operation {
+                name: name.to_string(),
+                path: "/".to_string(),
+                method: "get".to_string(),
+                tags: vec![tag.to_string()],
+                ..Operation::default()
+            });
+        }
+        let cfg = Config {
+            name: "Pay".to_string(),
+            dest: dir.path().to_path_buf(),
+            tag_clients: true,
+            ..Config::default()
+        };
+        let extras = calculate_extras(&spec);
+        write_request_module(&spec, &cfg, &extras, &mut Modified::default()).unwrap();
+        let read = |path: &str| fs::read_to_string(dir.path().join("src/request").join(path)).unwrap();
+        let request = read("mod.rs");
+        assert!(request.contains("pub mod type_;"));
+        assert!(request.contains("pub use type_::{TypeClient, ListTypesRequest};"));
+        assert!(request.contains("pub mod billing_payments;"));
+        assert!(read("type_/mod.rs").contains("pub fn type_(&self) -> TypeClient<'_> {"));
+        let billing = read("billing_payments/mod.rs");
+        assert!(billing.contains("pub struct BillingPaymentsClient<'a> {"));
+        assert!(billing.contains("pub fn billing_payments(&self) -> BillingPaymentsClient<'_> {"));
+        assert!(read("type_/list_types.rs").contains("impl<'a> super::TypeClient<'a> {"));
+    }
+}
@@ -0,0 +1,185 @@
+use convert_case::{Case, Casing};
+use hir::{Config, HirSpec, Record};
+use mir::Ty;
+use mir_rust::{ToRustIdent, ToRustType};
+use proc_macro2::TokenStream;
+use quote::quote;
+use serde_json::{Map, Value};
+
+use crate::features::ModelFeatures;
+use crate::mock::example_json;
+use crate::{write_rust, Modified};
+
+/// The file in `tests/` that `write_roundtrip_tests` writes.
+pub const ROUNDTRIP_TESTS: &str = "serde_examples.rs";
+
+/// Write `tests/serde_examples.rs`, which round-trips the spec's examples through the generated models.
+pub fn write_roundtrip_tests(spec: &HirSpec, cfg: &Config, modified: &mut Modified) -> std::io::Result<()> {
+    let Some(code) = make_roundtrip_tests(spec, cfg) else {
+        return Ok(());
+    };
+    let dest = cfg.dest.join("tests");
+    std::fs::create_dir_all(&dest)?;
+    write_rust(&dest.join(ROUNDTRIP_TESTS), code, modified)
+}
+
+/// Records with an example on the schema or on any of their fields.
+fn has_example(record: &Record) -> bool {
+    match record {
+        Record::Struct(s) => s.example.is_some() || s.fields.values().any(|f| f.example.is_some()),
+        Record::NewType(nt) => nt.fields.iter().any(|f| f.example.is_some()),
+        Record::TypeAlias(_, field) => field.example.is_some(),
+        Record::Enum(_) => false,
+    }
+}
+
+pub fn make_roundtrip_tests(spec: &HirSpec, cfg: &Config) -> Option<TokenStream> {
+    let records = spec.schemas.values().filter(|r| has_example(r)).collect::<Vec<_>>();
+    if records.is_empty() {
+        return None;
+    }
+    let package = cfg.package_name().to_rust_ident();
+    let allow_deprecated = if records.iter().any(|r| r.deprecated()) {
+        quote!(#![allow(deprecated)])
+    } else {
+        TokenStream::new()
+    };
+    let features = ModelFeatures::new(spec, cfg);
+    let tests = records.iter().map(|record| {
+        let ty = Ty::model(record.name());
+        let example = example_json(&ty, spec, &mut Vec::new());
+        let expected = serde_json::to_string(&expected_keys(&example, &ty, spec)).unwrap();
+        let example = serde_json::to_string(&example).unwrap();
+        let test = record.name().to_case(Case::Snake).to_rust_ident();
+        let model = ty.to_rust_type();
+        let feature = features.cfg(record.name());
+        quote! {
+            #feature
+            #[test]
+            fn #test() {
+                round_trip::<#package::model::#model>(#example, #expected);
+            }
+        }
+    });
+    Some(quote! {
+        //! Deserialize each example in the API spec into its model, and serialize it back, to check the serde attributes.
+        #allow_deprecated
+
+        /// `expected` is the example without the keys that are allowed to go missing, e.g. read-only properties.
+        fn round_trip<T: serde::Serialize + serde::de::DeserializeOwned>(example: &str, expected: &str) {
+            let example: serde_json::Value = serde_json::from_str(example).unwrap();
+            let expected: serde_json::Value = serde_json::from_str(expected).unwrap();
+            let value: T = serde_json::from_value(example).expect("Failed to deserialize the example");
+            let json = serde_json::to_value(&value).expect("Failed to serialize the example");
+            assert_keys(&expected, &json, "");
+        }
+
+        /// Every key in `expected` is in `actual`, with a value of the same JSON type. Values themselves can differ,
+        /// e.g. in how a date-time is written.
+        fn assert_keys(expected: &serde_json::Value, actual: &serde_json::Value, path: &str) {
+            match (expected, actual) {
+                (serde_json::Value::Object(expected), serde_json::Value::Object(actual)) => {
+                    for (key, value) in expected {
+                        let path = format!("{path}.{key}");
+                        match actual.get(key) {
+                            Some(actual) => assert_keys(value, actual, &path),
+                            None => panic!("{path} is missing after serializing"),
+                        }
+                    }
+                }
+                (serde_json::Value::Array(expected), serde_json::Value::Array(actual)) => {
+                    assert_eq!(expected.len(), actual.len(), "{path} changed length");
+                    for (i, (expected, actual)) in expected.iter().zip(actual).enumerate() {
+                        assert_keys(expected, actual, &format!("{path}[{i}]"));
+                    }
+                }
+                (serde_json::Value::Null, _)
+                | (serde_json::Value::String(_), serde_json::Value::String(_))
+                | (serde_json::Value::Number(_), serde_json::Value::Number(_))
+                | (serde_json::Value::Bool(_), serde_json::Value::Bool(_)) => {}
+                _ => panic!("{path} was {expected}, but serialized as {actual}"),
+            }
+        }
+
+        #(#tests)*
+    })
+}
+
+/// The keys of `value` that should survive a round trip through `ty`. Nulls, read-only properties, and keys the
+/// model doesn't have are left out.
+fn expected_keys(value: &Value, ty: &Ty, spec: &HirSpec) -> Value {
+    match (ty, value) {
+        (Ty::Model(name), _) => match spec.get_record(name) {
+            Ok(Record::Struct(s)) => {
+                let Value::Object(object) = value else {
+                    return value.clone();
+                };
+                let mut keys = Map::new();
+                for (key, value) in object {
+                    match s.fields.get(key) {
+                        Some(f) if !f.flatten && !f.read_only && !value.is_null() => {
+                            keys.insert(key.clone(), expected_keys(value, &f.ty, spec));
+                        }
+                        _ => {}
+                    }
+                }
+                for f in s.fields.values().filter(|f| f.flatten) {
+                    match &f.ty {
+                        Ty::Model(_) => {
+                            if let Value::Object(flattened) = expected_keys(value, &f.ty, spec) {
+                                keys.extend(flattened);
+                            }
+                        }
+                        Ty::HashMap(_) => {
+                            let extra = object
+                                .iter()
+                                .filter(|(k, v)| !s.fields.contains_key(*k) && !v.is_null())
+                                .map(|(k, v)| (k.clone(), v.clone()));
+                            keys.extend(extra);
+                        }
+                        _ => {}
+                    }
+                }
+                Value::Object(keys)
+            }
+            Ok(Record::NewType(nt)) if nt.fields.len() == 1 => expected_keys(value, &nt.fields[0].ty, spec),
+            Ok(Record::TypeAlias(_, field)) => expected_keys(value, &field.ty, spec),
+            _ => value.clone(),
+        },
+        (Ty::Array(inner), Value::Array(items)) => {
+            Value::Array(items.iter().map(|v| expected_keys(v, inner, spec)).collect())
+        }
+        (Ty::HashMap(inner), Value::Object(object)) => Value::Object(
+            object
+                .iter()
+                .map(|(k, v)| (k.clone(), expected_keys(v, inner, spec)))
+                .collect(),
+        ),
+        _ => value.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hir::{HirField, Struct};
+    use serde_json::json;
+
+    #[test]
+    fn test_expected_keys() {
+        let mut spec = HirSpec::default();
+        let mut id = HirField::new(Ty::String);
+        id.read_only = true;
+        spec.insert_schema(Struct {
+            name: "User".to_string(),
+            fields: [("id", id), ("nickname", HirField::new(Ty::String).nullable())]
+                .into_iter()
+                .map(|(n, f)| (n.to_string(), f))
+                .collect(),
+            ..Struct::default()
+        });
+        let example = json!([{"id": "u1", "nickname": null, "unknown": 1}, {"id": "u2", "nickname": "Ada"}]);
+        let ty = Ty::Array(Box::new(Ty::model("User")));
+        assert_eq!(expected_keys(&example, &ty, &spec), json!([{}, {"nickname": "Ada"}]));
+    }
+}
@@ -27,11 +27,29 @@ pub fn write_serde_module(extras: &Extras, path: &Path, modified: &mut Modified)
         .then(option_i64_str_module)
         .unwrap_or_default();
 
+    let base64_bytes = extras
+        .base64_bytes()
+        .then(option_base64_bytes_module)
+        .unwrap_or_default();
+
+    let binary_string = extras
+        .binary_string()
+        .then(option_binary_string_module)
+        .unwrap_or_default();
+
+    let iso8601_duration = extras
+        .iso8601_duration()
+        .then(option_iso8601_duration_module)
+        .unwrap_or_default();
+
     let code = quote! {
         pub use ::serde::*;
         #null_as_zero
         #date_as_int
         #int_as_str
+        #base64_bytes
+        #binary_string
+        #iso8601_duration
     };
     write_rust(&path, code, modified)
 }
@@ -47,3 +65,33 @@ pub fn option_i64_str_module() -> TokenStream {
 pub fn option_chrono_naive_date_as_int_module() -> TokenStream {
     TokenStream::from_str(include_str!("option_chrono_naive_date_as_int.rs")).unwrap()
 }
+
+pub fn option_base64_bytes_module() -> TokenStream {
+    TokenStream::from_str(include_str!("option_base64_bytes.rs")).unwrap()
+}
+
+pub fn option_binary_string_module() -> TokenStream {
+    TokenStream::from_str(include_str!("option_binary_string.rs")).unwrap()
+}
+
+pub fn option_iso8601_duration_module() -> TokenStream {
+    TokenStream::from_str(include_str!("option_iso8601_duration.rs")).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    include!("option_binary_string.rs");
+
+    #[test]
+    fn test_binary_string_roundtrip() {
+        let mut deserializer = serde_json::Deserializer::from_str(r#""%PDF-1.7""#);
+        let bytes = option_binary_string::deserialize(&mut deserializer).unwrap();
+        assert_eq!(bytes.as_deref(), Some(b"%PDF-1.7".as_slice()));
+        let json = option_binary_string::serialize(&bytes, serde_json::value::Serializer).unwrap();
+        assert_eq!(json, serde_json::json!("%PDF-1.7"));
+
+        let mut deserializer = serde_json::Deserializer::from_str("null");
+        assert_eq!(option_binary_string::deserialize(&mut deserializer).unwrap(), None);
+        assert!(option_binary_string::serialize(&Some(vec![0xff]), serde_json::value::Serializer).is_err());
+    }
+}
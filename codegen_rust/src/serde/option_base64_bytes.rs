@@ -0,0 +1,51 @@
+pub mod option_base64_bytes {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use serde::de::{Deserializer, Error, Unexpected};
+    use std::fmt;
+
+    struct Base64Visitor;
+
+    impl<'de> serde::de::Visitor<'de> for Base64Visitor {
+        type Value = Option<Vec<u8>>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            write!(formatter, "a base64 encoded string")
+        }
+
+        fn visit_str<E: Error>(self, value: &str) -> Result<Self::Value, E> {
+            STANDARD
+                .decode(value)
+                .map(Some)
+                .map_err(|_| Error::invalid_value(Unexpected::Str(value), &self))
+        }
+
+        fn visit_none<E: Error>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_unit<E: Error>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+            deserializer.deserialize_str(self)
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Vec<u8>>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_option(Base64Visitor)
+    }
+
+    pub fn serialize<S>(value: &Option<Vec<u8>>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match value {
+            Some(value) => serializer.serialize_str(&STANDARD.encode(value)),
+            None => serializer.serialize_none(),
+        }
+    }
+}
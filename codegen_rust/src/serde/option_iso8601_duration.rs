@@ -0,0 +1,76 @@
+pub mod option_iso8601_duration {
+    use serde::de::{Deserializer, Error, Unexpected};
+    use std::fmt;
+    use std::time::Duration;
+
+    struct DurationVisitor;
+
+    const DATE_UNITS: &[(char, f64)] = &[('W', 604800.0), ('D', 86400.0)];
+    const TIME_UNITS: &[(char, f64)] = &[('H', 3600.0), ('M', 60.0), ('S', 1.0)];
+
+    /// Parses durations like `P1W`, `P2DT3H` or `PT1.5S`. Years and months have no fixed length, so they are rejected.
+    fn parse(value: &str) -> Option<Duration> {
+        let rest = value.strip_prefix('P')?;
+        let (date, time) = rest.split_once('T').unwrap_or((rest, ""));
+        let mut secs = 0f64;
+        for (part, units) in [(date, DATE_UNITS), (time, TIME_UNITS)] {
+            let mut number = String::new();
+            for c in part.chars() {
+                if c.is_ascii_digit() || c == '.' {
+                    number.push(c);
+                    continue;
+                }
+                let (_, unit) = units.iter().find(|(u, _)| *u == c)?;
+                secs += number.parse::<f64>().ok()? * unit;
+                number.clear();
+            }
+            if !number.is_empty() {
+                return None;
+            }
+        }
+        Duration::try_from_secs_f64(secs).ok()
+    }
+
+    impl<'de> serde::de::Visitor<'de> for DurationVisitor {
+        type Value = Option<Duration>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            write!(formatter, "an ISO 8601 duration")
+        }
+
+        fn visit_str<E: Error>(self, value: &str) -> Result<Self::Value, E> {
+            parse(value)
+                .map(Some)
+                .ok_or_else(|| Error::invalid_value(Unexpected::Str(value), &self))
+        }
+
+        fn visit_none<E: Error>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_unit<E: Error>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+            deserializer.deserialize_str(self)
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_option(DurationVisitor)
+    }
+
+    pub fn serialize<S>(value: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match value {
+            Some(value) => serializer.serialize_str(&format!("PT{}S", value.as_secs_f64())),
+            None => serializer.serialize_none(),
+        }
+    }
+}
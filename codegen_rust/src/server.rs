@@ -0,0 +1,310 @@
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+
+use hir::{Config, HirField, HirSpec, Location, Operation, Parameter, Record};
+use mir::{DateSerialization, IntegerSerialization, Ty};
+use mir_rust::{deprecated_to_tokens, make_validate_method, ToRustCode, ToRustIdent, ToRustType};
+use proc_macro2::TokenStream;
+use quote::quote;
+
+use crate::features::{cfg_features, operation_cfg};
+use crate::{write_rust, Modified};
+
+/// Write `src/server.rs`, with an `Api` trait to implement and an axum router that calls it.
+pub fn write_server_module(spec: &HirSpec, cfg: &Config, modified: &mut Modified) -> std::io::Result<()> {
+    write_rust(&cfg.src().join("server.rs"), make_server_module(spec, cfg), modified)
+}
+
+pub fn make_server_module(spec: &HirSpec, cfg: &Config) -> TokenStream {
+    let doc = format!(
+        " Server side of the {} API: implement [`Api`], and serve it with [`router`].",
+        cfg.name
+    );
+    let models = spec
+        .operations
+        .iter()
+        .filter_map(|o| o.ret.inner_model())
+        .collect::<BTreeSet<_>>();
+    let import_models = if models.is_empty() {
+        TokenStream::new()
+    } else if cfg.tag_features {
+        let imports = models.iter().map(|m| {
+            let returning = spec.operations.iter().filter(|o| o.ret.inner_model() == Some(m));
+            // Imported whenever an operation returning it is compiled, so always if one of them is untagged.
+            let tags = returning.map(|o| o.tag()).collect::<Option<Vec<_>>>().unwrap_or_default();
+            let feature = cfg_features(tags, cfg);
+            let m = m.to_rust_struct();
+            quote! {
+                #feature
+                use crate::model::#m;
+            }
+        });
+        quote! { #(#imports)* }
+    } else {
+        let models = models.iter().map(|m| m.to_rust_struct());
+        quote! { use crate::model::{#(#models),*}; }
+    };
+    let methods = spec.operations.iter().map(|o| {
+        let doc = o.doc.clone().to_rust_code();
+        let deprecated = deprecated_to_tokens(o.deprecated);
+        let name = o.name.to_rust_ident();
+        let request = o.request_struct_name().to_rust_struct();
+        let ret = o.ret.to_rust_type();
+        let feature = operation_cfg(o, cfg);
+        quote! {
+            #doc
+            #deprecated
+            #feature
+            fn #name(
+                &self,
+                request: crate::request::#request,
+            ) -> impl Future<Output = Result<#ret, ApiError>> + Send;
+        }
+    });
+    let handlers = spec.operations.iter().map(|o| make_handler(o, spec, cfg));
+
+    let router = if cfg.tag_features {
+        // axum merges the methods of routes on the same path, so each can be behind its own feature.
+        let routes = spec.operations.iter().map(|operation| {
+            let feature = operation_cfg(operation, cfg);
+            let path = &operation.path;
+            let method = operation.method.to_lowercase().to_rust_ident();
+            let handler = operation.file_name().to_rust_ident();
+            quote! {
+                #feature
+                let router = router.route(#path, axum::routing::#method(#handler::<A>));
+            }
+        });
+        quote! {
+            let router = axum::Router::new();
+            #(#routes)*
+            router.with_state(Arc::new(api))
+        }
+    } else {
+        let mut paths: BTreeMap<&str, Vec<TokenStream>> = BTreeMap::new();
+        for operation in &spec.operations {
+            let method = operation.method.to_lowercase().to_rust_ident();
+            let handler = operation.file_name().to_rust_ident();
+            paths
+                .entry(&operation.path)
+                .or_default()
+                .push(quote! { #method(#handler::<A>) });
+        }
+        let routes = paths.into_iter().map(|(path, methods)| {
+            let mut methods = methods.into_iter();
+            let first = methods.next().unwrap();
+            let rest = methods.map(|m| quote! { .#m });
+            quote! {
+                .route(#path, axum::routing::#first #(#rest)*)
+            }
+        });
+        quote! {
+            axum::Router::new()
+                #(#routes)*
+                .with_state(Arc::new(api))
+        }
+    };
+
+    quote! {
+        #![doc = #doc]
+        #![allow(deprecated)]
+        use std::future::Future;
+        use std::sync::Arc;
+
+        use axum::body::Bytes;
+        use axum::extract::{Path, Query, State};
+        use axum::http::{HeaderMap, StatusCode};
+        use axum::response::IntoResponse;
+        use serde::de::DeserializeOwned;
+        use serde_json::{Map, Value};
+        #import_models
+
+        /// An error response, sent as `{"error": message}`.
+        #[derive(Debug, Clone)]
+        pub struct ApiError {
+            pub status: StatusCode,
+            pub message: String,
+        }
+
+        impl ApiError {
+            pub fn new(status: StatusCode, message: impl Into<String>) -> Self {
+                Self {
+                    status,
+                    message: message.into(),
+                }
+            }
+        }
+
+        impl IntoResponse for ApiError {
+            fn into_response(self) -> axum::response::Response {
+                (self.status, axum::Json(serde_json::json!({ "error": self.message }))).into_response()
+            }
+        }
+
+        /// One method per operation in the API spec. Requests that don't match the spec are rejected with
+        /// `400 Bad Request` before they get here.
+        pub trait Api: Send + Sync + 'static {
+            #(#methods)*
+        }
+
+        /// Route every operation to its method on `api`.
+        pub fn router<A: Api>(api: A) -> axum::Router {
+            #router
+        }
+
+        #(#handlers)*
+
+        #[derive(Clone, Copy)]
+        #[allow(dead_code)]
+        enum In {
+            Path,
+            Query,
+            Header,
+            Cookie,
+            Body,
+        }
+
+        struct Param {
+            /// The name in the request.
+            name: &'static str,
+            /// The field of the request struct.
+            field: &'static str,
+            location: In,
+            /// The value is JSON (e.g. a number), rather than a string.
+            json: bool,
+            repeated: bool,
+        }
+
+        /// Collect the parameters from the path, query, headers and JSON body, and deserialize the request struct from them.
+        fn parse_request<T: DeserializeOwned>(
+            params: &[Param],
+            path: Vec<(String, String)>,
+            query: Vec<(String, String)>,
+            headers: &HeaderMap,
+            body: &[u8],
+        ) -> Result<T, ApiError> {
+            let bad_request = |e: serde_json::Error| ApiError::new(StatusCode::BAD_REQUEST, e.to_string());
+            let body: Map<String, Value> = if body.is_empty() {
+                Map::new()
+            } else {
+                serde_json::from_slice(body).map_err(bad_request)?
+            };
+            let cookies = headers
+                .get_all(axum::http::header::COOKIE)
+                .iter()
+                .filter_map(|v| v.to_str().ok())
+                .flat_map(|v| v.split(';'))
+                .filter_map(|c| c.trim().split_once('='))
+                .collect::<Vec<_>>();
+            let mut object = Map::new();
+            for param in params {
+                let values: Vec<&str> = match param.location {
+                    In::Path => path.iter().filter(|(k, _)| k == param.name).map(|(_, v)| v.as_str()).collect(),
+                    In::Query => query.iter().filter(|(k, _)| k == param.name).map(|(_, v)| v.as_str()).collect(),
+                    In::Header => headers.get_all(param.name).iter().filter_map(|v| v.to_str().ok()).collect(),
+                    In::Cookie => cookies.iter().filter(|(k, _)| *k == param.name).map(|(_, v)| *v).collect(),
+                    In::Body => {
+                        if let Some(value) = body.get(param.name) {
+                            object.insert(param.field.to_string(), value.clone());
+                        }
+                        continue;
+                    }
+                };
+                let mut values = values.into_iter().map(|v| {
+                    if param.json {
+                        serde_json::from_str(v).unwrap_or_else(|_| Value::String(v.to_string()))
+                    } else {
+                        Value::String(v.to_string())
+                    }
+                });
+                if param.repeated {
+                    object.insert(param.field.to_string(), Value::Array(values.collect()));
+                } else if let Some(value) = values.next() {
+                    object.insert(param.field.to_string(), value);
+                }
+            }
+            serde_json::from_value(Value::Object(object)).map_err(bad_request)
+        }
+    }
+}
+
+fn make_handler(operation: &Operation, spec: &HirSpec, cfg: &Config) -> TokenStream {
+    let name = operation.file_name().to_rust_ident();
+    let method = operation.name.to_rust_ident();
+    let request = operation.request_struct_name().to_rust_struct();
+    let params = operation.parameters.iter().map(|p| {
+        let name = &p.name;
+        let field = p.name.to_rust_ident().0;
+        let location = match p.location {
+            Location::Path => quote!(In::Path),
+            Location::Query => quote!(In::Query),
+            Location::Header => quote!(In::Header),
+            Location::Cookie => quote!(In::Cookie),
+            Location::Body => quote!(In::Body),
+        };
+        let repeated = p.ty.is_iterable() && p.location != Location::Body;
+        let json = is_json(p.ty.inner_iterable().unwrap_or(&p.ty), spec);
+        quote! {
+            Param { name: #name, field: #field, location: #location, json: #json, repeated: #repeated }
+        }
+    });
+    let (path_arg, path) = if operation.parameters.iter().any(|p| p.location == Location::Path) {
+        (quote!(Path(path): Path<Vec<(String, String)>>,), quote!(path))
+    } else {
+        (TokenStream::new(), quote!(Vec::new()))
+    };
+    let validate = if has_validate(&operation.parameters, spec) && !cfg.skip_validation {
+        quote! {
+            if let Err(e) = request.validate() {
+                return ApiError::new(StatusCode::BAD_REQUEST, e.to_string()).into_response();
+            }
+        }
+    } else {
+        TokenStream::new()
+    };
+    let feature = operation_cfg(operation, cfg);
+    quote! {
+        #feature
+        async fn #name<A: Api>(
+            State(api): State<Arc<A>>,
+            #path_arg
+            Query(query): Query<Vec<(String, String)>>,
+            headers: HeaderMap,
+            body: Bytes,
+        ) -> axum::response::Response {
+            let params = &[#(#params),*];
+            let request: crate::request::#request = match parse_request(params, #path, query, &headers, &body) {
+                Ok(request) => request,
+                Err(e) => return e.into_response(),
+            };
+            #validate
+            match api.#method(request).await {
+                Ok(response) => axum::Json(response).into_response(),
+                Err(e) => e.into_response(),
+            }
+        }
+    }
+}
+
+/// Whether the request struct has a `validate()` method.
+fn has_validate(parameters: &[Parameter], spec: &HirSpec) -> bool {
+    let fields = parameters
+        .iter()
+        .map(|p| (p.name.clone(), HirField::from(p)))
+        .collect::<Vec<_>>();
+    make_validate_method(&fields, spec).is_some()
+}
+
+/// Whether a path, query, header or cookie value of this type is written as JSON, rather than as a string.
+fn is_json(ty: &Ty, spec: &HirSpec) -> bool {
+    match ty {
+        Ty::Integer { ser, .. } => *ser != IntegerSerialization::String,
+        Ty::Float { .. } | Ty::Boolean => true,
+        Ty::Date { ser } => *ser == DateSerialization::Integer,
+        Ty::Model(name) => match spec.get_record(name) {
+            Ok(Record::Enum(e)) => e.is_integer(),
+            _ => false,
+        },
+        _ => false,
+    }
+}
@@ -0,0 +1,291 @@
+use std::collections::BTreeSet;
+
+use hir::{Config, HirSpec, HmacAlgorithm, SignatureEncoding, WebhookEvent, WebhookSignature};
+use mir::Ty;
+use mir_rust::{deprecated_to_tokens, ToRustCode, ToRustIdent, ToRustType};
+use proc_macro2::TokenStream;
+use quote::quote;
+
+use crate::{write_rust, Modified};
+
+/// Write `src/webhook.rs`, with an enum of the webhooks the API sends and functions to parse and verify them.
+pub fn write_webhook_module(spec: &HirSpec, cfg: &Config, modified: &mut Modified) -> std::io::Result<()> {
+    write_rust(&cfg.src().join("webhook.rs"), make_webhook_module(spec, cfg), modified)
+}
+
+pub fn make_webhook_module(spec: &HirSpec, cfg: &Config) -> TokenStream {
+    let doc = if spec.webhook_signature.is_some() {
+        format!(
+            " Webhooks the {} API sends. Check a request with [`verify_webhook_signature`], then parse it with \
+            [`parse_webhook`].",
+            cfg.name
+        )
+    } else {
+        format!(
+            " Webhooks the {} API sends. Parse a request with [`parse_webhook`].",
+            cfg.name
+        )
+    };
+    let models = spec
+        .webhooks
+        .iter()
+        .filter_map(|w| w.body.inner_model())
+        .collect::<BTreeSet<_>>();
+    let import_models = if models.is_empty() {
+        TokenStream::new()
+    } else {
+        let models = models.iter().map(|m| m.to_rust_struct());
+        quote! { use crate::model::{#(#models),*}; }
+    };
+    let variants = spec.webhooks.iter().map(|w| {
+        let doc = w.doc.clone().to_rust_code();
+        let deprecated = deprecated_to_tokens(w.deprecated);
+        let name = w.name.to_rust_struct();
+        let body = match &w.body {
+            Ty::Unit => TokenStream::new(),
+            ty => {
+                let ty = ty.to_rust_type();
+                quote!((#ty))
+            }
+        };
+        quote! {
+            #doc
+            #deprecated
+            #name #body
+        }
+    });
+    let allow_deprecated = if spec.webhooks.iter().any(|w| w.deprecated) {
+        quote!(#![allow(deprecated)])
+    } else {
+        TokenStream::new()
+    };
+    let parse_webhook = make_parse_webhook(spec);
+    let verify_signature = spec
+        .webhook_signature
+        .as_ref()
+        .map(make_verify_signature)
+        .unwrap_or_default();
+
+    quote! {
+        #![doc = #doc]
+        #allow_deprecated
+        #import_models
+
+        /// A webhook request, by event.
+        #[derive(Debug, Clone)]
+        pub enum WebhookEvent {
+            #(#variants,)*
+        }
+
+        #[derive(Debug)]
+        pub enum WebhookError {
+            /// The request doesn't say which event it is.
+            MissingEvent,
+            /// The event isn't one in the API spec.
+            UnknownEvent(String),
+            /// The request doesn't say which event it is, and its body matches each of these.
+            AmbiguousEvent(Vec<&'static str>),
+            /// The signature is missing, or doesn't match the body.
+            InvalidSignature,
+            Json(serde_json::Error),
+        }
+
+        impl std::fmt::Display for WebhookError {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                match self {
+                    WebhookError::MissingEvent => write!(f, "The webhook doesn't say which event it is"),
+                    WebhookError::UnknownEvent(event) => write!(f, "Unknown webhook event: {event}"),
+                    WebhookError::AmbiguousEvent(events) => {
+                        write!(f, "The webhook could be any of these events: {}", events.join(", "))
+                    }
+                    WebhookError::InvalidSignature => write!(f, "Invalid webhook signature"),
+                    WebhookError::Json(e) => write!(f, "Invalid webhook body: {e}"),
+                }
+            }
+        }
+
+        impl std::error::Error for WebhookError {}
+
+        impl From<serde_json::Error> for WebhookError {
+            fn from(e: serde_json::Error) -> Self {
+                WebhookError::Json(e)
+            }
+        }
+
+        #parse_webhook
+        #verify_signature
+    }
+}
+
+fn make_parse_webhook(spec: &HirSpec) -> TokenStream {
+    let parse = |name: &str, ty: &Ty, value: TokenStream| {
+        let name = name.to_rust_struct();
+        match ty {
+            Ty::Unit => quote!(WebhookEvent::#name),
+            _ => quote!(WebhookEvent::#name(#value?)),
+        }
+    };
+    match &spec.webhook_event {
+        Some(WebhookEvent::Header(header)) => {
+            let doc = format!(" Parse a webhook request, using the `{header}` header to tell which event it is.");
+            let arms = spec.webhooks.iter().map(|w| {
+                let event = &w.event;
+                let value = parse(&w.name, &w.body, quote!(serde_json::from_slice(body)));
+                quote!(#event => Ok(#value))
+            });
+            quote! {
+                #[doc = #doc]
+                pub fn parse_webhook(body: &[u8], headers: &http::HeaderMap) -> Result<WebhookEvent, WebhookError> {
+                    let event = headers
+                        .get(#header)
+                        .and_then(|v| v.to_str().ok())
+                        .ok_or(WebhookError::MissingEvent)?;
+                    match event {
+                        #(#arms,)*
+                        _ => Err(WebhookError::UnknownEvent(event.to_string())),
+                    }
+                }
+            }
+        }
+        Some(WebhookEvent::Field(field)) => {
+            let doc =
+                format!(" Parse a webhook request, using the `{field}` field of the body to tell which event it is.");
+            let arms = spec.webhooks.iter().map(|w| {
+                let event = &w.event;
+                let value = parse(&w.name, &w.body, quote!(serde_json::from_value(value)));
+                quote!(#event => Ok(#value))
+            });
+            quote! {
+                #[doc = #doc]
+                pub fn parse_webhook(body: &[u8], _headers: &http::HeaderMap) -> Result<WebhookEvent, WebhookError> {
+                    let value: serde_json::Value = serde_json::from_slice(body)?;
+                    let event = value
+                        .get(#field)
+                        .and_then(|v| v.as_str())
+                        .ok_or(WebhookError::MissingEvent)?
+                        .to_string();
+                    match event.as_str() {
+                        #(#arms,)*
+                        _ => Err(WebhookError::UnknownEvent(event)),
+                    }
+                }
+            }
+        }
+        None => {
+            let attempts = spec.webhooks.iter().map(|w| {
+                let name = w.name.to_rust_struct();
+                let event = &w.event;
+                match &w.body {
+                    Ty::Unit => quote! {
+                        if body.is_empty() {
+                            matches.push((#event, WebhookEvent::#name));
+                        }
+                    },
+                    _ => quote! {
+                        if let Ok(event) = serde_json::from_slice(body) {
+                            matches.push((#event, WebhookEvent::#name(event)));
+                        }
+                    },
+                }
+            });
+            quote! {
+                /// Parse a webhook request into the event its body deserializes as. The API spec doesn't say how to
+                /// tell events apart, so a body that deserializes as more than one is an error.
+                pub fn parse_webhook(body: &[u8], _headers: &http::HeaderMap) -> Result<WebhookEvent, WebhookError> {
+                    let mut matches = Vec::new();
+                    #(#attempts)*
+                    if matches.len() > 1 {
+                        let events = matches.into_iter().map(|(event, _)| event).collect();
+                        return Err(WebhookError::AmbiguousEvent(events));
+                    }
+                    matches.pop().map(|(_, event)| event).ok_or(WebhookError::MissingEvent)
+                }
+            }
+        }
+    }
+}
+
+fn make_verify_signature(signature: &WebhookSignature) -> TokenStream {
+    let WebhookSignature {
+        header,
+        algorithm,
+        encoding,
+        prefix,
+    } = signature;
+    let (hash, hash_name) = match algorithm {
+        HmacAlgorithm::Sha1 => (quote!(sha1::Sha1), "SHA1"),
+        HmacAlgorithm::Sha256 => (quote!(sha2::Sha256), "SHA256"),
+        HmacAlgorithm::Sha512 => (quote!(sha2::Sha512), "SHA512"),
+    };
+    let doc =
+        format!(" Check the `{header}` header against an HMAC-{hash_name} of the body, keyed with the webhook secret.");
+    let (decode, decode_fn) = match encoding {
+        SignatureEncoding::Hex => (
+            quote!(decode_hex(signature)),
+            quote! {
+                fn decode_hex(s: &str) -> Option<Vec<u8>> {
+                    s.as_bytes()
+                        .chunks(2)
+                        .map(|c| match c {
+                            [_, _] => u8::from_str_radix(std::str::from_utf8(c).ok()?, 16).ok(),
+                            _ => None,
+                        })
+                        .collect()
+                }
+            },
+        ),
+        SignatureEncoding::Base64 => (
+            quote!(base64::Engine::decode(&base64::engine::general_purpose::STANDARD, signature).ok()),
+            TokenStream::new(),
+        ),
+    };
+    let strip_prefix = if prefix.is_empty() {
+        TokenStream::new()
+    } else {
+        quote!(.and_then(|v| v.strip_prefix(#prefix)))
+    };
+    quote! {
+        #[doc = #doc]
+        pub fn verify_webhook_signature(body: &[u8], headers: &http::HeaderMap, secret: &[u8]) -> Result<(), WebhookError> {
+            use hmac::Mac;
+            let signature = headers
+                .get(#header)
+                .and_then(|v| v.to_str().ok())
+                #strip_prefix
+                .ok_or(WebhookError::InvalidSignature)?;
+            let signature = #decode.ok_or(WebhookError::InvalidSignature)?;
+            let mut mac = hmac::Hmac::<#hash>::new_from_slice(secret).expect("HMAC takes a key of any length");
+            mac.update(body);
+            mac.verify_slice(&signature).map_err(|_| WebhookError::InvalidSignature)
+        }
+
+        #decode_fn
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hir::Webhook;
+    use mir_rust::format_code;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_webhook_without_event() {
+        let mut spec = HirSpec::default();
+        for (name, event) in [("NewPet", "pet.created"), ("NewOwner", "owner.created")] {
+            spec.webhooks.push(Webhook {
+                name: name.to_string(),
+                event: event.to_string(),
+                doc: None,
+                body: Ty::Model(name.trim_start_matches("New").to_string()),
+                deprecated: false,
+            });
+        }
+        let code = format_code(make_parse_webhook(&spec));
+        assert!(code.contains("matches.push((\"pet.created\", WebhookEvent::NewPet(event)));"));
+        assert!(code.contains("matches.push((\"owner.created\", WebhookEvent::NewOwner(event)));"));
+        assert!(code.contains("return Err(WebhookError::AmbiguousEvent(events));"));
+        assert!(code.contains(".ok_or(WebhookError::MissingEvent)"));
+    }
+}
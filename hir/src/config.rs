@@ -1,6 +1,7 @@
 use convert_case::{Case, Casing};
-use mir::{Ident, Literal};
+use mir::{Ident, Literal, StringFormat};
 use std::path::PathBuf;
+use std::str::FromStr;
 
 #[derive(Debug, Clone, Default)]
 pub struct Config {
@@ -9,6 +10,59 @@ pub struct Config {
     pub derives: Vec<String>,
     pub build_examples: bool,
     pub ormlite: bool,
+    /// Ignore `format` on numbers, and generate every integer as i64 and every float as f64.
+    pub widen_numbers: bool,
+    /// String formats to generate rich types for (e.g. uuid::Uuid). Other formats are generated as String.
+    pub string_formats: Vec<StringFormat>,
+    /// Don't call `validate()` on request params before sending. The methods are still generated.
+    pub skip_validation: bool,
+    /// Which enums get an `Unknown(String)` variant, so new values from the API don't fail deserialization.
+    pub open_enums: OpenEnums,
+    /// Generate `{Name}Create` request models without read-only fields, instead of skipping them when serializing.
+    pub split_read_write: bool,
+    /// Leave out operations marked `deprecated` in the spec.
+    pub exclude_deprecated: bool,
+    /// Also generate an axum server in `{dest}/mock` that answers every operation with example data.
+    pub mock_server: bool,
+    /// Also generate `src/server.rs`, with an `Api` trait and axum router, behind the `server` feature.
+    pub server: bool,
+    /// Operations to retry even though their method isn't idempotent.
+    pub retry: Vec<String>,
+    /// Operations never to retry.
+    pub no_retry: Vec<String>,
+    /// The header to send a random idempotency key in, for POST and PATCH requests.
+    pub idempotency_key: Option<String>,
+    /// Put tagged operations on a sub-client per tag (`client.account().list()`), with their request modules under
+    /// `request::{tag}`.
+    pub tag_clients: bool,
+    /// Put each tag's operations, and the models only they use, behind a cargo feature, and add the features to
+    /// `Cargo.toml`.
+    pub tag_features: bool,
+    /// Write `tests/serde_examples.rs`, which round-trips the spec's `example` values through the models.
+    pub example_tests: bool,
+}
+
+/// Which enums get an `Unknown(String)` variant. Enums with an `x-enum-closed` extension are left as marked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OpenEnums {
+    /// Enums used in responses, including in nested models.
+    #[default]
+    Responses,
+    All,
+    None,
+}
+
+impl FromStr for OpenEnums {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "responses" => Ok(OpenEnums::Responses),
+            "all" => Ok(OpenEnums::All),
+            "none" => Ok(OpenEnums::None),
+            _ => Err(format!("Unknown value {s}. Expected one of: responses, all, none")),
+        }
+    }
 }
 
 impl Config {
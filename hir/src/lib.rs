@@ -1,7 +1,8 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 /// The API model.
 /// Higher level compared to code level models in ln-model.
 use std::fmt::Debug;
+use std::fs;
 use std::fs::File;
 use std::io;
 use std::io::Write;
@@ -12,17 +13,20 @@ use std::string::{String, ToString};
 use anyhow::Result;
 use convert_case::{Case, Casing};
 use openapiv3 as oa;
+use tracing::debug;
 
-pub use config::Config;
+pub use config::{Config, OpenEnums};
 pub use lang::*;
 use mir::parameter::ParamKey;
 use mir::Doc;
-use mir::Ty;
+use mir::{StringFormat, Ty};
 pub use operation::*;
+pub use webhook::*;
 
 mod config;
 mod lang;
 mod operation;
+mod webhook;
 
 /// Parameter is an input to an OpenAPI operation.
 #[derive(Debug, Clone)]
@@ -33,6 +37,10 @@ pub struct Parameter {
     pub optional: bool,
     pub doc: Option<Doc>,
     pub example: Option<serde_json::Value>,
+    pub constraints: Constraints,
+    /// The schema `default`, which the server assumes when the parameter is omitted.
+    pub default: Option<serde_json::Value>,
+    pub deprecated: bool,
 }
 
 impl Parameter {
@@ -52,6 +60,9 @@ impl Parameter {
             optional: false,
             doc: None,
             example: None,
+            constraints: Constraints::default(),
+            default: None,
+            deprecated: false,
         }
     }
 }
@@ -115,6 +126,28 @@ pub struct Oauth2Auth {
     pub scopes: Vec<(String, String)>,
 }
 
+/// Validation keywords from the schema (`minLength`, `maximum`, `uniqueItems`, ...).
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Constraints {
+    pub min_length: Option<usize>,
+    pub max_length: Option<usize>,
+    pub pattern: Option<String>,
+    pub minimum: Option<f64>,
+    pub maximum: Option<f64>,
+    pub exclusive_minimum: bool,
+    pub exclusive_maximum: bool,
+    pub multiple_of: Option<f64>,
+    pub min_items: Option<usize>,
+    pub max_items: Option<usize>,
+    pub unique_items: bool,
+}
+
+impl Constraints {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct HirField {
     pub ty: Ty,
@@ -122,6 +155,16 @@ pub struct HirField {
     pub doc: Option<Doc>,
     pub example: Option<serde_json::Value>,
     pub flatten: bool,
+    pub constraints: Constraints,
+    /// Set by the server, so never sent in requests.
+    pub read_only: bool,
+    /// Only sent in requests, so never present in responses.
+    pub write_only: bool,
+    /// The schema `default`, used when the field is missing.
+    pub default: Option<serde_json::Value>,
+    /// The field closes a cycle of models, so it needs indirection (e.g. `Box`) to have a finite size.
+    pub boxed: bool,
+    pub deprecated: bool,
 }
 
 impl HirField {
@@ -132,6 +175,12 @@ impl HirField {
             doc: None,
             example: None,
             flatten: false,
+            constraints: Constraints::default(),
+            read_only: false,
+            write_only: false,
+            default: None,
+            boxed: false,
+            deprecated: false,
         }
     }
 
@@ -147,6 +196,9 @@ pub struct Struct {
     pub nullable: bool,
     pub fields: BTreeMap<String, HirField>,
     pub docs: Option<Doc>,
+    pub deprecated: bool,
+    /// The schema-level `example`.
+    pub example: Option<serde_json::Value>,
 }
 
 impl Into<Record> for Struct {
@@ -160,6 +212,7 @@ pub struct NewType {
     pub name: String,
     pub fields: Vec<HirField>,
     pub doc: Option<Doc>,
+    pub deprecated: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -173,6 +226,8 @@ pub struct TypeAlias {
 pub struct Variant {
     pub value: String,
     pub alias: Option<String>,
+    /// The number for a variant of an integer enum. `value` holds it as a string.
+    pub discriminant: Option<i64>,
 }
 
 #[derive(Debug, Clone)]
@@ -180,13 +235,24 @@ pub struct Enum {
     pub name: String,
     pub variants: Vec<Variant>,
     pub doc: Option<Doc>,
+    /// Whether to add an `Unknown(String)` variant for values not in the spec. `None` until set by an
+    /// `x-enum-closed` extension or [`HirSpec::resolve_open_enums`].
+    pub open: Option<bool>,
+    pub deprecated: bool,
 }
 
 impl Enum {
+    /// Integer enums serialize each variant as its `discriminant`, rather than as a string.
+    pub fn is_integer(&self) -> bool {
+        !self.variants.is_empty() && self.variants.iter().all(|v| v.discriminant.is_some())
+    }
+
     pub fn iter_safe_variant_names(&self) -> impl Iterator<Item = (String, &str)> + '_ {
         self.variants.iter().map(|v| {
             let n = v.alias.as_ref().unwrap_or(&v.value);
-            if n.chars().next().unwrap().is_numeric() {
+            if let Some(abs) = n.strip_prefix('-').filter(|n| n.starts_with(|c: char| c.is_numeric())) {
+                (format!("{}Minus{abs}", self.name), v.value.as_str())
+            } else if n.chars().next().unwrap().is_numeric() {
                 (format!("{}{n}", self.name), v.value.as_str())
             } else {
                 (n.to_string(), v.value.as_str())
@@ -226,6 +292,12 @@ impl From<TypeAlias> for Record {
                 doc: None,
                 example: None,
                 flatten: false,
+                constraints: Constraints::default(),
+                read_only: false,
+                write_only: false,
+                default: None,
+                boxed: false,
+                deprecated: false,
             },
         )
     }
@@ -241,6 +313,15 @@ impl Record {
         }
     }
 
+    pub fn deprecated(&self) -> bool {
+        match self {
+            Record::Struct(s) => s.deprecated,
+            Record::Enum(e) => e.deprecated,
+            Record::NewType(n) => n.deprecated,
+            Record::TypeAlias(_, field) => field.deprecated,
+        }
+    }
+
     pub fn len_fields(&self) -> usize {
         match self {
             Record::Struct(s) => s.fields.len(),
@@ -301,6 +382,10 @@ pub struct HirSpec {
     pub security: Vec<AuthStrategy>,
 
     pub api_docs_url: Option<String>,
+
+    pub webhooks: Vec<Webhook>,
+    pub webhook_event: Option<WebhookEvent>,
+    pub webhook_signature: Option<WebhookSignature>,
 }
 
 impl HirSpec {
@@ -337,6 +422,30 @@ pub fn qualified_env_var(service: &str, var_name: &str) -> String {
     format!("{} {}", service, var_name).to_case(Case::ScreamingSnake)
 }
 
+/// Remove the last occurrence of the tag's words, singular or plural, from a snake case name.
+fn strip_tag(name: &str, tag: &str) -> Option<String> {
+    let words = name.split('_').collect::<Vec<_>>();
+    let tag = tag.to_case(Case::Snake);
+    let mut forms = vec![format!("{tag}s"), format!("{tag}es"), tag.clone()];
+    if let Some(stem) = tag.strip_suffix('y') {
+        forms.push(format!("{stem}ies"));
+    }
+    if let Some(singular) = tag.strip_suffix('s') {
+        forms.push(singular.to_string());
+    }
+    for end in (1..=words.len()).rev() {
+        for form in &forms {
+            let tag_words = form.split('_').collect::<Vec<_>>();
+            if end < tag_words.len() || words[end - tag_words.len()..end] != tag_words[..] {
+                continue;
+            }
+            let rest = [&words[..end - tag_words.len()], &words[end..]].concat();
+            return if rest.is_empty() { None } else { Some(rest.join("_")) };
+        }
+    }
+    None
+}
+
 impl HirSpec {
     pub fn get_record(&self, name: &str) -> Result<&Record> {
         self.schemas
@@ -351,6 +460,75 @@ impl HirSpec {
             .ok_or_else(|| anyhow::anyhow!("No operation named {}", name))
     }
 
+    /// Tagged operations, grouped by their first tag.
+    pub fn operations_by_tag(&self) -> BTreeMap<&str, Vec<&Operation>> {
+        let mut tags = BTreeMap::<_, Vec<_>>::new();
+        for operation in &self.operations {
+            if let Some(tag) = operation.tag() {
+                tags.entry(tag).or_default().push(operation);
+            }
+        }
+        tags
+    }
+
+    /// The tags of the operations that use each model, directly or through other models. Models that untagged
+    /// operations or webhooks use, or that nothing uses, are left out, since they're always needed.
+    pub fn model_tags(&self) -> BTreeMap<&str, BTreeSet<&str>> {
+        let mut tags: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+        let mut untagged = HashSet::new();
+        for operation in &self.operations {
+            let types = once(&operation.ret)
+                .chain(operation.parameters.iter().map(|p| &p.ty))
+                .chain(operation.response_headers.iter().map(|h| &h.ty));
+            let models = self.reachable_models(types);
+            match operation.tag() {
+                Some(tag) => models.into_iter().for_each(|m| {
+                    tags.entry(m).or_default().insert(tag);
+                }),
+                None => untagged.extend(models),
+            }
+        }
+        untagged.extend(self.reachable_models(self.webhooks.iter().map(|w| &w.body)));
+        tags.retain(|m, _| !untagged.contains(m));
+        tags
+    }
+
+    /// The models the types refer to, and the models those refer to, and so on.
+    fn reachable_models<'a>(&'a self, types: impl Iterator<Item = &'a Ty>) -> HashSet<&'a str> {
+        let mut queue = types.filter_map(|ty| ty.inner_model()).collect::<Vec<_>>();
+        let mut models = HashSet::new();
+        while let Some(name) = queue.pop() {
+            if !models.insert(name.as_str()) {
+                continue;
+            }
+            if let Some(record) = self.schemas.get(name) {
+                queue.extend(record.fields().filter_map(|f| f.ty.inner_model()));
+            }
+        }
+        models
+    }
+
+    /// The operation's snake case name on its tag's sub-client, without the tag, e.g. `list` for `ListAccounts`
+    /// tagged `account`. It keeps the whole name if nothing is left, or another operation with the tag would get the
+    /// same name.
+    pub fn tag_method_name(&self, operation: &Operation) -> String {
+        let Some(tag) = operation.tag() else {
+            return operation.file_name();
+        };
+        let short = |o: &Operation| strip_tag(&o.file_name(), tag).unwrap_or_else(|| o.file_name());
+        let name = short(operation);
+        let taken = self
+            .operations
+            .iter()
+            .filter(|o| o.tag() == Some(tag) && o.name != operation.name)
+            .any(|o| short(o) == name);
+        if taken {
+            operation.file_name()
+        } else {
+            name
+        }
+    }
+
     pub fn server_strategy(&self) -> ServerStrategy {
         let len = self.servers.len();
         if len == 0 {
@@ -397,6 +575,276 @@ impl HirSpec {
         self.security.iter().any(|s| matches!(s, AuthStrategy::Token(_)))
     }
 
+    /// Discard integer width and float precision everywhere in the spec.
+    pub fn widen_numbers(&mut self) {
+        for record in self.schemas.values_mut() {
+            for field in record.fields_mut() {
+                field.ty.widen_numbers();
+            }
+        }
+        for operation in &mut self.operations {
+            operation.ret.widen_numbers();
+            for param in &mut operation.parameters {
+                param.ty.widen_numbers();
+            }
+            for header in &mut operation.response_headers {
+                header.ty.widen_numbers();
+            }
+        }
+        for webhook in &mut self.webhooks {
+            webhook.body.widen_numbers();
+        }
+    }
+
+    /// Decide which enums get an `Unknown` variant, for those not already marked by an extension.
+    pub fn resolve_open_enums(&mut self, mode: OpenEnums) {
+        let responses = match mode {
+            OpenEnums::Responses => self.response_models(),
+            _ => HashSet::new(),
+        };
+        for record in self.schemas.values_mut() {
+            let Record::Enum(e) = record else {
+                continue;
+            };
+            if e.open.is_none() {
+                e.open = Some(match mode {
+                    OpenEnums::All => true,
+                    OpenEnums::Responses => responses.contains(&e.name),
+                    OpenEnums::None => false,
+                });
+            }
+        }
+    }
+
+    /// Names of all records reachable from an operation's return type or a webhook body.
+    fn response_models(&self) -> HashSet<String> {
+        let mut seen = HashSet::new();
+        let mut stack = self
+            .operations
+            .iter()
+            .filter_map(|o| o.ret.inner_model())
+            .chain(self.webhooks.iter().filter_map(|w| w.body.inner_model()))
+            .cloned()
+            .collect::<Vec<_>>();
+        while let Some(name) = stack.pop() {
+            if !seen.insert(name.clone()) {
+                continue;
+            }
+            if let Some(record) = self.schemas.get(&name) {
+                stack.extend(record.fields().filter_map(|f| f.ty.inner_model()).cloned());
+            }
+        }
+        seen
+    }
+
+    /// Give each model sent in a request, that has read-only or write-only fields (directly or in a nested
+    /// model), a `{Name}Create` copy without the read-only fields, and use it for request params. Write-only
+    /// fields are then dropped from the original models, which are only used in responses.
+    pub fn split_read_write(&mut self) {
+        let mut requested = HashSet::new();
+        let mut stack = self
+            .operations
+            .iter()
+            .flat_map(|o| &o.parameters)
+            .filter_map(|p| p.ty.inner_model())
+            .cloned()
+            .collect::<Vec<_>>();
+        while let Some(name) = stack.pop() {
+            if !requested.insert(name.clone()) {
+                continue;
+            }
+            if let Some(record) = self.schemas.get(&name) {
+                stack.extend(record.fields().filter_map(|f| f.ty.inner_model()).cloned());
+            }
+        }
+
+        let mut split = HashSet::new();
+        loop {
+            let before = split.len();
+            for name in &requested {
+                let Some(Record::Struct(s)) = self.schemas.get(name) else {
+                    continue;
+                };
+                if split.contains(name) || self.schemas.contains_key(&format!("{name}Create")) {
+                    continue;
+                }
+                let needs_split = s.fields.values().any(|f| {
+                    f.read_only || f.write_only || f.ty.inner_model().is_some_and(|m| split.contains(m))
+                });
+                if needs_split {
+                    split.insert(name.clone());
+                }
+            }
+            if split.len() == before {
+                break;
+            }
+        }
+
+        let creates = split
+            .iter()
+            .filter_map(|name| self.schemas.get(name)?.as_struct())
+            .map(|s| {
+                let mut create = s.clone();
+                create.name = format!("{}Create", s.name);
+                create.fields.retain(|_, f| !f.read_only);
+                for f in create.fields.values_mut() {
+                    f.write_only = false;
+                    use_create_models(&mut f.ty, &split);
+                }
+                create
+            })
+            .collect::<Vec<_>>();
+        for record in self.schemas.values_mut() {
+            if let Record::Struct(s) = record {
+                if split.contains(&s.name) || !requested.contains(&s.name) {
+                    s.fields.retain(|_, f| !f.write_only);
+                }
+            }
+        }
+        for create in creates {
+            self.schemas.insert(create.name.clone(), Record::Struct(create));
+        }
+        for operation in &mut self.operations {
+            for param in &mut operation.parameters {
+                use_create_models(&mut param.ty, &split);
+            }
+        }
+    }
+
+    /// Mark the fields that close a cycle of models, e.g. `Comment.parent: Comment`, or `A.b: B` and `B.a: A`.
+    /// Only direct (or optional) model fields count; arrays and maps are already heap allocated.
+    pub fn mark_recursive_fields(&mut self) {
+        let mut done = HashSet::new();
+        let mut back_edges = Vec::new();
+        for name in self.schemas.keys() {
+            self.find_back_edges(name, &mut Vec::new(), &mut done, &mut back_edges);
+        }
+        for (name, index) in back_edges {
+            if let Some(field) = self.schemas.get_mut(&name).and_then(|r| r.fields_mut().nth(index)) {
+                field.boxed = true;
+            }
+        }
+    }
+
+    /// Depth-first search collecting (record, field index) for each field pointing back into `path`.
+    fn find_back_edges(
+        &self,
+        name: &str,
+        path: &mut Vec<String>,
+        done: &mut HashSet<String>,
+        back_edges: &mut Vec<(String, usize)>,
+    ) {
+        if done.contains(name) {
+            return;
+        }
+        let Some(record) = self.schemas.get(name) else {
+            return;
+        };
+        path.push(name.to_string());
+        for (index, field) in record.fields().enumerate() {
+            let Ty::Model(target) = &field.ty else {
+                continue;
+            };
+            if path.contains(target) {
+                back_edges.push((name.to_string(), index));
+            } else {
+                self.find_back_edges(target, path, done, back_edges);
+            }
+        }
+        path.pop();
+        done.insert(name.to_string());
+    }
+
+    /// Give POST and PATCH operations an optional `header` parameter with an idempotency key, if `header` is set,
+    /// along with operations marked `x-idempotent: true`. Operations that already have the header use it, which is an
+    /// error unless it's a plain string, since the key is a random UUID string. Call after `retain_string_formats`.
+    pub fn add_idempotency_keys(&mut self, header: Option<&str>) -> Result<()> {
+        for operation in &mut self.operations {
+            let unsafe_method = matches!(operation.method.to_lowercase().as_str(), "post" | "patch");
+            if !operation.idempotent.unwrap_or(header.is_some() && unsafe_method) {
+                continue;
+            }
+            let header = header.unwrap_or("Idempotency-Key");
+            let existing = operation
+                .parameters
+                .iter()
+                .find(|p| p.location == Location::Header && p.name.eq_ignore_ascii_case(header));
+            match existing {
+                // The caller has to pass it already.
+                Some(p) if !p.optional => continue,
+                Some(p) if matches!(p.ty, Ty::String) => operation.idempotency_key = Some(p.name.clone()),
+                Some(p) => anyhow::bail!(
+                    "{}: the {} header can't hold an idempotency key, because it isn't a string: {:?}",
+                    operation.name,
+                    p.name,
+                    p.ty
+                ),
+                None => {
+                    operation.parameters.push(Parameter {
+                        name: header.to_string(),
+                        ty: Ty::String,
+                        location: Location::Header,
+                        optional: true,
+                        doc: Some(Doc(
+                            "A unique key, so the server can tell a retry of this request from a new one. A random \
+                            UUID is used if it isn't set."
+                                .to_string(),
+                        )),
+                        example: None,
+                        constraints: Constraints::default(),
+                        default: None,
+                        deprecated: false,
+                    });
+                    operation.parameters.sort_by(|a, b| a.name.cmp(&b.name));
+                    operation.idempotency_key = Some(header.to_string());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Turn retries on or off for the named operations, overriding their method and `x-retry`.
+    pub fn override_retries(&mut self, operations: &[String], enabled: bool) -> Result<()> {
+        for name in operations {
+            let pascal = name.replace('.', "_").to_case(Case::Pascal);
+            let operation = self
+                .operations
+                .iter_mut()
+                .find(|o| o.name == pascal)
+                .ok_or_else(|| anyhow::anyhow!("No operation named {}", name))?;
+            operation.retry.enabled = Some(enabled);
+        }
+        Ok(())
+    }
+
+    /// Keep only the given string formats, and use plain strings for the rest. Decoded formats
+    /// (e.g. base64 bytes) are only kept on struct fields, where a serde helper can be attached.
+    pub fn retain_string_formats(&mut self, formats: &[StringFormat]) {
+        let enabled = |f: StringFormat| formats.contains(&f);
+        let textual = |f: StringFormat| formats.contains(&f) && !f.is_decoded();
+        for record in self.schemas.values_mut() {
+            let keep: &dyn Fn(StringFormat) -> bool = match record {
+                Record::Struct(_) => &enabled,
+                _ => &textual,
+            };
+            for field in record.fields_mut() {
+                field.ty.erase_string_formats(keep);
+            }
+        }
+        for operation in &mut self.operations {
+            operation.ret.erase_string_formats(&textual);
+            for param in &mut operation.parameters {
+                param.ty.erase_string_formats(&textual);
+            }
+            for header in &mut operation.response_headers {
+                header.ty.erase_string_formats(&textual);
+            }
+        }
+        for webhook in &mut self.webhooks {
+            webhook.body.erase_string_formats(&textual);
+        }
+    }
+
     pub fn oauth2_auth(&self) -> Option<&Oauth2Auth> {
         self.security
             .iter()
@@ -408,6 +856,14 @@ impl HirSpec {
     }
 }
 
+fn use_create_models(ty: &mut Ty, split: &HashSet<String>) {
+    match ty {
+        Ty::Model(name) if split.contains(name) => name.push_str("Create"),
+        Ty::Array(inner) | Ty::HashMap(inner) => use_create_models(inner, split),
+        _ => {}
+    }
+}
+
 impl From<&Parameter> for HirField {
     fn from(p: &Parameter) -> Self {
         Self {
@@ -416,13 +872,45 @@ impl From<&Parameter> for HirField {
             doc: p.doc.clone(),
             example: p.example.clone(),
             flatten: false,
+            constraints: p.constraints.clone(),
+            read_only: false,
+            write_only: false,
+            default: p.default.clone(),
+            boxed: false,
+            deprecated: p.deprecated,
         }
     }
 }
 
-pub fn write_file(path: &Path, text: &str) -> io::Result<()> {
+/// Write the file, unless it already has this content, so that regenerating only touches (and makes cargo rebuild)
+/// what changed. Returns whether it was written.
+pub fn write_file(path: &Path, text: &str) -> io::Result<bool> {
+    if fs::read(path).is_ok_and(|existing| existing == text.as_bytes()) {
+        return Ok(false);
+    }
     let mut f = File::create(path)?;
     f.write_all(text.as_bytes())?;
-    println!("{}: Wrote file.", path.display());
-    Ok(())
+    debug!("{}: Wrote file.", path.display());
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_file_skips_unchanged() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("lib.rs");
+        assert!(write_file(&path, "pub struct A;\n").unwrap());
+        let modified = fs::metadata(&path).unwrap().modified().unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+
+        assert!(!write_file(&path, "pub struct A;\n").unwrap());
+        assert_eq!(fs::metadata(&path).unwrap().modified().unwrap(), modified);
+
+        assert!(write_file(&path, "pub struct B;\n").unwrap());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "pub struct B;\n");
+        assert_ne!(fs::metadata(&path).unwrap().modified().unwrap(), modified);
+    }
 }
@@ -10,6 +10,31 @@ pub struct Operation {
     pub ret: Ty,
     pub path: String,
     pub method: String,
+    pub deprecated: bool,
+    pub retry: RetryOverride,
+    /// From `x-idempotent`: whether the operation gets an idempotency key, whatever its method and the config.
+    pub idempotent: Option<bool>,
+    /// The header parameter that is set to a random key when the caller doesn't set it, so the server can tell a
+    /// retry from a new request.
+    pub idempotency_key: Option<String>,
+    /// Headers declared on the operation's successful responses.
+    pub response_headers: Vec<ResponseHeader>,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ResponseHeader {
+    pub name: String,
+    pub ty: Ty,
+    pub doc: Option<Doc>,
+}
+
+/// An operation's change to the client's retry policy, from the `x-retry` extension or `--retry`/`--no-retry`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RetryOverride {
+    /// `Some(true)` retries the operation even if its method isn't idempotent, and `Some(false)` never retries it.
+    pub enabled: Option<bool>,
+    pub max_retries: Option<u32>,
 }
 
 impl Operation {
@@ -34,6 +59,21 @@ impl Operation {
         self.parameters.iter().filter(|p| !p.optional).count() > 3
     }
 
+    /// Whether the operation is retried, absent an override. Retrying a POST or PATCH could repeat its effect,
+    /// unless it has an idempotency key.
+    pub fn is_idempotent(&self) -> bool {
+        self.idempotency_key.is_some()
+            || matches!(
+                self.method.to_lowercase().as_str(),
+                "get" | "head" | "put" | "delete" | "options" | "trace"
+            )
+    }
+
+    /// The first tag, which picks the sub-client the operation goes on.
+    pub fn tag(&self) -> Option<&str> {
+        self.tags.first().map(|t| t.as_str())
+    }
+
     pub fn has_response(&self) -> bool {
         !matches!(self.ret, Ty::Unit)
     }
@@ -86,6 +126,8 @@ impl Operation {
             name: self.required_struct_name(),
             fields,
             docs: None,
+            deprecated: false,
+            example: None,
         }
     }
 }
@@ -99,6 +141,12 @@ impl Default for Operation {
             ret: Ty::Unit,
             path: "".to_string(),
             method: "".to_string(),
+            deprecated: false,
+            retry: RetryOverride::default(),
+            idempotent: None,
+            idempotency_key: None,
+            response_headers: Vec::new(),
+            tags: Vec::new(),
         }
     }
 }
@@ -0,0 +1,46 @@
+use mir::{Doc, Ty};
+
+/// A request the API sends to the user's server, from OpenAPI 3.1 `webhooks` or an operation's `callbacks`.
+#[derive(Debug, Clone)]
+pub struct Webhook {
+    pub name: String,
+    /// The value that identifies this webhook, in the header or body field named by [`WebhookEvent`].
+    pub event: String,
+    pub doc: Option<Doc>,
+    pub body: Ty,
+    pub deprecated: bool,
+}
+
+/// Where a webhook request says which webhook it is, from the spec's `x-webhook-event` extension.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WebhookEvent {
+    Header(String),
+    /// A top-level field of the JSON body.
+    Field(String),
+}
+
+/// How webhook requests are signed, from the spec's `x-webhook-signature` extension. The signature is an HMAC of
+/// the raw body, keyed with the webhook secret.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebhookSignature {
+    pub header: String,
+    pub algorithm: HmacAlgorithm,
+    pub encoding: SignatureEncoding,
+    /// Text before the signature in the header, e.g. `sha256=`.
+    pub prefix: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HmacAlgorithm {
+    Sha1,
+    #[default]
+    Sha256,
+    Sha512,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SignatureEncoding {
+    #[default]
+    Hex,
+    Base64,
+}
@@ -0,0 +1,45 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use hir::Config;
+use libninja::{default, extractor::extract_spec};
+use openapiv3::OpenAPI;
+
+fn recurly() -> OpenAPI {
+    serde_yaml::from_str(include_str!("../../test_specs/recurly.yaml")).unwrap()
+}
+
+fn config(dest: &std::path::Path) -> Config {
+    Config {
+        name: "Recurly".to_string(),
+        dest: dest.to_path_buf(),
+        example_tests: true,
+        ..default()
+    }
+}
+
+fn bench_extract(c: &mut Criterion) {
+    let spec = recurly();
+    c.bench_function("extract recurly", |b| b.iter(|| extract_spec(&spec).unwrap()));
+}
+
+fn bench_generate(c: &mut Criterion) {
+    let spec = extract_spec(&recurly()).unwrap();
+    let mut group = c.benchmark_group("generate recurly");
+    group.sample_size(10);
+    group.bench_function("empty dir", |b| {
+        b.iter_batched(
+            || tempfile::tempdir().unwrap(),
+            |temp| codegen_rust::generate_rust_library(spec.clone(), config(temp.path())).unwrap(),
+            BatchSize::PerIteration,
+        )
+    });
+    // Regenerating over unchanged output, which writes nothing.
+    let temp = tempfile::tempdir().unwrap();
+    codegen_rust::generate_rust_library(spec.clone(), config(temp.path())).unwrap();
+    group.bench_function("unchanged", |b| {
+        b.iter(|| codegen_rust::generate_rust_library(spec.clone(), config(temp.path())).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_extract, bench_generate);
+criterion_main!(benches);
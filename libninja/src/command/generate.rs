@@ -1,11 +1,14 @@
-use crate::extractor::extract_spec;
-use anyhow::{anyhow, Result};
+use crate::extractor::{preserve_webhooks, OperationFilter};
+use crate::Generator;
+use anyhow::{anyhow, bail, Result};
 use clap::{Args, ValueEnum};
 use convert_case::{Case, Casing};
-use hir::{Config, Language};
+use hir::{Config, Language, OpenEnums};
+use mir::StringFormat;
 use openapiv3::{OpenAPI, VersionedOpenAPI};
 use std::fs::File;
 use std::path::{Path, PathBuf};
+use tracing::info;
 
 /// CLI flags
 #[derive(ValueEnum, Debug, Clone, Copy)]
@@ -14,6 +17,16 @@ pub enum Flag {
     Ormlite,
     /// Only used by Rust (for now). Adds fake::Dummy flags to the code.
     Fake,
+    /// Ignore `format` on numbers, and generate every integer as i64 and every float as f64.
+    WidenNumbers,
+    /// Don't call `validate()` on request params before sending requests.
+    SkipValidation,
+    /// Generate `{Name}Create` request models without read-only fields.
+    SplitReadWrite,
+    /// Leave out operations marked `deprecated` in the spec.
+    ExcludeDeprecated,
+    /// Error when operations have the same name, instead of renaming all but the first.
+    StrictNames,
 }
 
 #[derive(Args, Debug)]
@@ -37,6 +50,70 @@ pub struct Generate {
     #[clap(long)]
     derive: Vec<String>,
 
+    /// String formats to generate rich types for: uuid, uri, email, ip, byte, binary, duration.
+    /// Formats not listed are generated as String.
+    #[clap(long = "string-format")]
+    string_formats: Vec<StringFormat>,
+
+    /// Which enums get an `Unknown(String)` variant for values not in the spec: responses, all, or none.
+    #[clap(long, default_value = "responses")]
+    open_enums: OpenEnums,
+
+    /// Also generate a mock server crate in `{output_dir}/mock`, which serves example responses for every operation.
+    #[clap(long)]
+    mock_server: bool,
+
+    /// Also generate `src/server.rs`, with a trait to implement the API and an axum router for it.
+    /// It's behind the `server` cargo feature.
+    #[clap(long)]
+    server: bool,
+
+    /// Retry this operation on 429 and 5xx responses even though its method (e.g. POST) isn't idempotent.
+    /// Can be repeated.
+    #[clap(long)]
+    retry: Vec<String>,
+
+    /// Never retry this operation. Can be repeated.
+    #[clap(long)]
+    no_retry: Vec<String>,
+
+    /// Send a random idempotency key with POST and PATCH requests, in this header (default `Idempotency-Key`).
+    #[clap(long, num_args = 0..=1, require_equals = true, default_missing_value = "Idempotency-Key")]
+    idempotency_key: Option<String>,
+
+    /// Group operations by their first tag into sub-clients, e.g. `client.account().list()` instead of
+    /// `client.list_accounts()`. Untagged operations stay on the client.
+    #[clap(long)]
+    tag_clients: bool,
+
+    /// Put each tag's operations, and the models only they use, behind a cargo feature named after the tag. The
+    /// features are added to the library's `Cargo.toml`, and all enabled by default.
+    #[clap(long)]
+    tag_features: bool,
+
+    /// Write `tests/serde_examples.rs` into the library, with a test per model that has `example` values in the spec,
+    /// which deserializes the example and serializes it back.
+    #[clap(long)]
+    example_tests: bool,
+
+    /// Remove this prefix from operation names, e.g. `api_v1` turns `api_v1_list_users` into `list_users`.
+    /// Can be repeated.
+    #[clap(long)]
+    strip_prefix: Vec<String>,
+
+    /// Only generate operations with these tags, and the models they use. Comma separated, or repeated.
+    #[clap(long, value_delimiter = ',')]
+    include_tags: Vec<String>,
+
+    /// Only generate operations whose path matches these globs, e.g. `/accounts/**`, and the models they use.
+    /// `*` matches within a path segment. Comma separated, or repeated.
+    #[clap(long, value_delimiter = ',')]
+    include_paths: Vec<String>,
+
+    /// Don't generate these operations, by operationId. Comma separated, or repeated.
+    #[clap(long, value_delimiter = ',')]
+    exclude_operations: Vec<String>,
+
     /// The "service" name. E.g. if we want to generate a library for the Stripe API, this would be "Stripe".
     name: String,
 
@@ -46,34 +123,75 @@ pub struct Generate {
 
 impl Generate {
     pub fn run(self) -> Result<()> {
-        let spec = PathBuf::from(self.spec_filepath);
-        let spec = read_spec(&spec)?;
+        let filter = OperationFilter {
+            include_tags: self.include_tags,
+            include_paths: self.include_paths,
+            exclude_operations: self.exclude_operations,
+        };
+        let strict = self.config.iter().any(|f| matches!(f, Flag::StrictNames));
         let output_dir = PathBuf::from(self.output_dir.unwrap_or_else(|| ".".to_string()));
-        let spec = extract_spec(&spec)?;
         let config = Config {
             name: self.name.to_case(Case::Pascal),
             dest: output_dir,
             derives: self.derive,
             build_examples: self.examples,
             ormlite: false,
+            widen_numbers: self.config.iter().any(|f| matches!(f, Flag::WidenNumbers)),
+            skip_validation: self.config.iter().any(|f| matches!(f, Flag::SkipValidation)),
+            split_read_write: self.config.iter().any(|f| matches!(f, Flag::SplitReadWrite)),
+            exclude_deprecated: self.config.iter().any(|f| matches!(f, Flag::ExcludeDeprecated)),
+            string_formats: self.string_formats,
+            open_enums: self.open_enums,
+            mock_server: self.mock_server,
+            server: self.server,
+            retry: self.retry,
+            no_retry: self.no_retry,
+            idempotency_key: self.idempotency_key,
+            tag_clients: self.tag_clients,
+            tag_features: self.tag_features,
+            example_tests: self.example_tests,
+        };
+        let mut generator = Generator::from_path(self.spec_filepath)
+            .config(config)
+            .filter(filter)
+            .strict_names(strict);
+        for prefix in self.strip_prefix {
+            generator = generator.strip_prefix(prefix);
+        }
+        let report = match self.language {
+            Language::Rust => generator.generate()?,
         };
-        match self.language {
-            Language::Rust => codegen_rust::generate_rust_library(spec, config),
+        for path in &report.removed {
+            info!("{}: Removed unused file.", path.display());
         }
+        info!(
+            "Wrote {} files. {} were unchanged, and {} removed.",
+            report.written.len(),
+            report.unchanged.len(),
+            report.removed.len()
+        );
+        Ok(())
     }
 }
 
+/// Read a YAML or JSON spec, by the file's extension, defaulting to YAML.
 pub fn read_spec(path: &Path) -> Result<OpenAPI> {
     let file = File::open(path).map_err(|_| anyhow!("{:?}: OpenAPI file not found.", path))?;
-    let ext = path
-        .extension()
-        .map(|s| s.to_str().expect("Extension isn't utf8"))
-        .unwrap_or_else(|| "yaml");
-    let openapi: VersionedOpenAPI = match ext {
-        "yaml" => serde_yaml::from_reader(file)?,
+    let openapi: serde_json::Value = match path.extension().and_then(|s| s.to_str()).unwrap_or("yaml") {
+        "yaml" | "yml" => serde_yaml::from_reader(file)?,
         "json" => serde_json::from_reader(file)?,
-        _ => panic!("Unknown file extension"),
+        ext => bail!("{:?}: Unknown file extension {ext}. Expected yaml or json.", path),
     };
-    let openapi = openapi.upgrade();
-    Ok(openapi)
+    spec_from_value(openapi)
+}
+
+/// Parse a YAML or JSON spec.
+pub fn parse_spec(spec: &str) -> Result<OpenAPI> {
+    spec_from_value(serde_yaml::from_str(spec)?)
+}
+
+fn spec_from_value(mut openapi: serde_json::Value) -> Result<OpenAPI> {
+    preserve_webhooks(&mut openapi);
+    let openapi: VersionedOpenAPI = serde_json::from_value(openapi)?;
+    Ok(openapi.upgrade())
 }
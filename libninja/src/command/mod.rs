@@ -1,5 +1,5 @@
 mod generate;
 mod init;
 
-pub use generate::Generate;
+pub use generate::{parse_spec, read_spec, Generate};
 pub use init::Init;
@@ -0,0 +1,201 @@
+use anyhow::{bail, Result};
+use convert_case::{Case, Casing};
+use openapiv3::{OpenAPI, Operation};
+use regex::Regex;
+
+use crate::extractor::naming::operation_name;
+
+/// Which operations to generate. Schemas only the left out operations use are then removed by treeshaking.
+#[derive(Debug, Clone, Default)]
+pub struct OperationFilter {
+    /// Keep operations with any of these tags.
+    pub include_tags: Vec<String>,
+    /// Keep operations whose path matches any of these globs. `*` matches within a path segment, and `**` across
+    /// them.
+    pub include_paths: Vec<String>,
+    /// Leave out these operations, by `x-libninja-name` or `operationId`.
+    pub exclude_operations: Vec<String>,
+}
+
+impl OperationFilter {
+    pub fn is_empty(&self) -> bool {
+        self.include_tags.is_empty() && self.include_paths.is_empty() && self.exclude_operations.is_empty()
+    }
+}
+
+/// Remove the operations the filter leaves out from the spec. Without `include_tags` or `include_paths`, every
+/// operation is included, and otherwise, operations matching either are. A tag, glob or operation that matches
+/// nothing is an error, since it's most likely a typo.
+pub fn filter_operations(spec: &mut OpenAPI, filter: &OperationFilter) -> Result<()> {
+    if filter.is_empty() {
+        return Ok(());
+    }
+    let globs = filter
+        .include_paths
+        .iter()
+        .map(|g| glob_to_regex(g))
+        .collect::<Vec<_>>();
+    let excluded = filter
+        .exclude_operations
+        .iter()
+        .map(|name| name.replace('.', "_").to_case(Case::Pascal))
+        .collect::<Vec<_>>();
+    for tag in &filter.include_tags {
+        if !spec.operations().any(|(_, _, op, _)| op.tags.contains(tag)) {
+            bail!("No operation is tagged {tag}");
+        }
+    }
+    for (glob, regex) in filter.include_paths.iter().zip(&globs) {
+        if !spec.paths.paths.keys().any(|path| regex.is_match(path)) {
+            bail!("No path matches {glob}");
+        }
+    }
+    for (name, pascal) in filter.exclude_operations.iter().zip(&excluded) {
+        let exists = spec
+            .operations()
+            .any(|(path, method, op, _)| &operation_name(op, method, path).to_case(Case::Pascal) == pascal);
+        if !exists {
+            bail!("No operation named {name}");
+        }
+    }
+
+    let include_all = filter.include_tags.is_empty() && filter.include_paths.is_empty();
+    let keep = |path: &str, method: &str, op: &Operation| {
+        let included = include_all
+            || op.tags.iter().any(|t| filter.include_tags.contains(t))
+            || globs.iter().any(|r| r.is_match(path));
+        included && !excluded.contains(&operation_name(op, method, path).to_case(Case::Pascal))
+    };
+    retain_operations(spec, keep);
+    Ok(())
+}
+
+/// Remove the operations marked `deprecated` from the spec, so treeshaking also removes schemas only they use.
+pub fn remove_deprecated_operations(spec: &mut OpenAPI) {
+    retain_operations(spec, |_, _, op| !op.deprecated);
+}
+
+/// Remove the operations `keep` returns false for, and then paths left without operations.
+fn retain_operations(spec: &mut OpenAPI, keep: impl Fn(&str, &str, &Operation) -> bool) {
+    for (path, item) in spec.paths.paths.iter_mut() {
+        let Some(item) = item.as_mut() else {
+            continue;
+        };
+        let slots = [
+            ("get", &mut item.get),
+            ("put", &mut item.put),
+            ("post", &mut item.post),
+            ("delete", &mut item.delete),
+            ("options", &mut item.options),
+            ("head", &mut item.head),
+            ("patch", &mut item.patch),
+            ("trace", &mut item.trace),
+        ];
+        for (method, slot) in slots {
+            if slot.as_ref().is_some_and(|op| !keep(path, method, op)) {
+                *slot = None;
+            }
+        }
+    }
+    spec.paths
+        .paths
+        .retain(|_, item| item.as_item().is_none_or(|i| i.iter().next().is_some()));
+}
+
+fn glob_to_regex(glob: &str) -> Regex {
+    let mut regex = "^".to_string();
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                regex.push_str(".*");
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    Regex::new(&regex).expect("An escaped glob is a valid regex")
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_yaml::from_str;
+
+    use super::*;
+
+    #[test]
+    fn test_filter_operations() {
+        let mut spec: OpenAPI = from_str(
+            "openapi: 3.0.0
+info: {title: test, version: '1'}
+paths:
+  /accounts:
+    get: {operationId: listAccounts, tags: [account], responses: {'200': {description: ok}}}
+    post: {operationId: createAccount, tags: [account], responses: {'200': {description: ok}}}
+  /accounts/{id}/notes:
+    get: {operationId: listNotes, tags: [note], responses: {'200': {description: ok}}}
+  /sites/{id}:
+    get: {operationId: getSite, tags: [site], responses: {'200': {description: ok}}}
+  /plans:
+    get: {operationId: listPlans, tags: [plan], responses: {'200': {description: ok}}}",
+        )
+        .unwrap();
+        let filter = OperationFilter {
+            include_tags: vec!["account".to_string()],
+            include_paths: vec!["/sites/*".to_string()],
+            exclude_operations: vec!["create_account".to_string()],
+        };
+        filter_operations(&mut spec, &filter).unwrap();
+        let names = spec
+            .operations()
+            .map(|(_, _, op, _)| op.operation_id.as_deref().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["listAccounts", "getSite"]);
+        assert_eq!(spec.paths.paths.len(), 2);
+
+        let filter = OperationFilter {
+            include_paths: vec!["/nope/**".to_string()],
+            ..OperationFilter::default()
+        };
+        assert_eq!(
+            filter_operations(&mut spec, &filter).unwrap_err().to_string(),
+            "No path matches /nope/**"
+        );
+    }
+
+    #[test]
+    fn test_remove_deprecated_operations() {
+        let mut spec: OpenAPI = from_str(
+            "openapi: 3.0.0
+info: {title: test, version: '1'}
+paths:
+  /accounts:
+    get: {operationId: listAccounts, responses: {'200': {description: ok}}}
+  /legacy:
+    get:
+      operationId: getLegacy
+      deprecated: true
+      responses:
+        '200': {description: ok, content: {application/json: {schema: {$ref: '#/components/schemas/Legacy'}}}}
+components:
+  schemas:
+    Legacy: {type: object, properties: {id: {type: string}}}",
+        )
+        .unwrap();
+        remove_deprecated_operations(&mut spec);
+        assert_eq!(spec.paths.paths.keys().collect::<Vec<_>>(), ["/accounts"]);
+        let hir = crate::extractor::extract_spec(&spec).unwrap();
+        assert!(hir.get_record("Legacy").is_err());
+    }
+
+    #[test]
+    fn test_glob_to_regex() {
+        assert!(glob_to_regex("/accounts/*").is_match("/accounts/{id}"));
+        assert!(!glob_to_regex("/accounts/*").is_match("/accounts/{id}/notes"));
+        assert!(glob_to_regex("/accounts/**").is_match("/accounts/{id}/notes"));
+        assert!(glob_to_regex("/accounts/{id}").is_match("/accounts/{id}"));
+    }
+}
@@ -1,6 +1,6 @@
 use std::collections::{BTreeMap, HashMap, HashSet};
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use convert_case::{Case, Casing};
 use openapiv3 as oa;
 use openapiv3::{APIKeyLocation, OpenAPI, Schema, SecurityScheme};
@@ -12,15 +12,23 @@ pub use record::*;
 pub use ty::{schema_ref_to_ty, schema_ref_to_ty2, schema_to_ty, *};
 
 use operation::extract_operation;
+pub use filter::{filter_operations, remove_deprecated_operations, OperationFilter};
+pub use naming::name_operations;
+pub use webhook::preserve_webhooks;
+use webhook::extract_webhooks;
 
+mod filter;
+mod naming;
 mod operation;
 pub mod plural;
 mod record;
 mod ty;
+mod webhook;
 
 pub fn extract_spec(spec: &OpenAPI) -> Result<HirSpec> {
     let mut hir = extract_without_treeshake(spec)?;
     treeshake(&mut hir);
+    hir.mark_recursive_fields();
     validate(&hir);
     debug!("Extracted {} schemas: {:?}", hir.schemas.len(), hir.schemas.keys());
     Ok(hir)
@@ -34,13 +42,17 @@ pub fn extract_without_treeshake(spec: &OpenAPI) -> Result<HirSpec> {
     // we do some "create new schema" operations, and if those new ones overwrite
     // the built in ones, that leads to confusion.
     for (name, schema) in &spec.components.schemas {
-        let schema = schema.as_item().expect("Expected schema, not reference");
+        let Some(schema) = schema.as_item() else {
+            bail!("components.schemas.{name}: Expected a schema, not a reference.");
+        };
         extract_schema(&name, schema, spec, &mut hir);
     }
 
     for (path, method, operation, item) in spec.operations() {
         extract_operation(spec, path, method, operation, item, &mut hir);
     }
+    extract_webhooks(spec, &mut hir);
+    hoist_inline_objects(spec, &mut hir);
 
     let servers = extract_servers(spec)?;
     let security = extract_security_strategies(spec);
@@ -109,29 +121,31 @@ fn extract_api_docs_link(spec: &OpenAPI) -> Option<String> {
     spec.external_docs.as_ref().map(|e| e.url.clone())
 }
 
-/// Remove from the HirSpec anything that appears to be unused
+/// Remove the models that no operation or webhook uses, directly or through other models.
 fn remove_unused(spec: &mut HirSpec) {
+    let mut queue: Vec<&String> = Vec::new();
+    for operation in spec.operations.iter() {
+        queue.extend(operation.ret.inner_model());
+        queue.extend(operation.parameters.iter().filter_map(|p| p.ty.inner_model()));
+        queue.extend(operation.response_headers.iter().filter_map(|h| h.ty.inner_model()));
+    }
+    queue.extend(spec.webhooks.iter().filter_map(|w| w.body.inner_model()));
+    // Specs without `webhooks` often still describe their payloads as `*Webhook` schemas.
+    if spec.webhooks.is_empty() {
+        queue.extend(spec.schemas.keys().filter(|name| name.ends_with("Webhook")));
+    }
     let mut used: HashSet<String> = HashSet::new();
-    for (_name, schema) in spec.schemas.iter() {
-        for field in schema.fields() {
-            if let Some(name) = &field.ty.inner_model() {
-                used.insert(name.to_string());
-            };
+    while let Some(name) = queue.pop() {
+        if !used.insert(name.clone()) {
+            continue;
         }
-    }
-    for operation in spec.operations.iter() {
-        if let Some(name) = &operation.ret.inner_model() {
-            used.insert(name.to_string());
-        };
-        for param in operation.parameters.iter() {
-            if let Some(name) = &param.ty.inner_model() {
-                used.insert(name.to_string());
-            };
+        if let Some(schema) = spec.schemas.get(name) {
+            queue.extend(schema.fields().filter_map(|f| f.ty.inner_model()));
         }
     }
     let count_before = spec.schemas.len();
     spec.schemas.retain(|name, _| {
-        let needed = used.contains(name) || name.ends_with("Webhook");
+        let needed = used.contains(name);
         if !needed {
             debug!("Removing unused schema: {}", name);
         }
@@ -175,10 +189,6 @@ fn treeshake(spec: &mut HirSpec) {
 
     // Remove unused models
     remove_unused(spec);
-    // Do it twice for 2nd layer of unused models. Super cheap way to remove models
-    // that are only unused recursively. E.g. A -> B. A is removed on first pass, B
-    // but B isn't. On second pass, B is removed.
-    remove_unused(spec);
 }
 
 pub fn spec_defines_auth(spec: &HirSpec) -> bool {
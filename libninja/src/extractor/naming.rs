@@ -0,0 +1,183 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+use anyhow::{bail, Result};
+use convert_case::{Case, Casing};
+use openapiv3::{OpenAPI, Operation};
+use tracing::warn;
+
+use crate::extractor::operation::make_name;
+
+/// The name from the `x-libninja-name` extension, which is used as is.
+fn name_override(op: &Operation) -> Option<&str> {
+    op.extensions.get("x-libninja-name").and_then(|v| v.as_str())
+}
+
+/// The name from `x-libninja-name`, then `operationId`, then the method and path.
+pub fn operation_name(op: &Operation, method: &str, path: &str) -> String {
+    match name_override(op) {
+        Some(name) => name.to_string(),
+        None => make_name(op.operation_id.as_ref(), method, path),
+    }
+}
+
+struct Named {
+    label: String,
+    name: String,
+    fixed: bool,
+}
+
+/// Settle every operation's name before extraction, and set its `operationId` to it.
+///
+/// Names that don't come from `x-libninja-name` lose the first of `strip_prefixes` they start with. If two operations
+/// get the same name, the first keeps it, and the others are named after their method and full path, with a number
+/// appended if that's taken too. With `strict`, that's an error instead, as is two `x-libninja-name`s clashing.
+pub fn name_operations(spec: &mut OpenAPI, strip_prefixes: &[String], strict: bool) -> Result<()> {
+    let mut named = spec
+        .operations()
+        .map(|(path, method, op, _)| {
+            let fixed = name_override(op).is_some();
+            let mut name = operation_name(op, method, path);
+            if !fixed {
+                name = strip_prefix(&name, strip_prefixes);
+            }
+            Named {
+                label: format!("{} {path}", method.to_uppercase()),
+                name,
+                fixed,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mut by_name: HashMap<String, Vec<&Named>> = HashMap::new();
+    for n in &named {
+        by_name.entry(key(&n.name)).or_default().push(n);
+    }
+    let mut collisions = by_name
+        .iter()
+        .filter(|(_, ops)| ops.len() > 1 && (strict || ops.iter().filter(|n| n.fixed).count() > 1))
+        .collect::<Vec<_>>();
+    if !collisions.is_empty() {
+        collisions.sort_by(|a, b| a.0.cmp(b.0));
+        let mut report = "Operations have the same name:\n".to_string();
+        for (name, ops) in collisions {
+            let labels = ops.iter().map(|n| n.label.as_str()).collect::<Vec<_>>();
+            writeln!(report, "  {name}: {}", labels.join(", "))?;
+        }
+        report.push_str("Rename them with the `x-libninja-name` extension.");
+        bail!(report);
+    }
+
+    let mut taken = named
+        .iter()
+        .filter(|n| n.fixed)
+        .map(|n| key(&n.name))
+        .collect::<HashSet<_>>();
+    let paths = spec.operations().map(|(path, method, _, _)| (path, method));
+    for (n, (path, method)) in named.iter_mut().zip(paths) {
+        if n.fixed || taken.insert(key(&n.name)) {
+            continue;
+        }
+        let base = strip_prefix(&full_path_name(method, path), strip_prefixes);
+        let mut name = base.clone();
+        let mut i = 2;
+        while !taken.insert(key(&name)) {
+            name = format!("{base}_{i}");
+            i += 1;
+        }
+        warn!("{} is also named {}. Naming it {name} instead.", n.label, n.name);
+        n.name = name;
+    }
+
+    for (n, (_, _, op)) in named.into_iter().zip(spec.operations_mut()) {
+        op.operation_id = Some(n.name);
+    }
+    Ok(())
+}
+
+/// Names that generate the same identifiers, e.g. `listUsers` and `list_users`, are the same.
+fn key(name: &str) -> String {
+    name.to_case(Case::Pascal)
+}
+
+fn strip_prefix(name: &str, prefixes: &[String]) -> String {
+    let snake = name.to_case(Case::Snake);
+    for prefix in prefixes {
+        let prefix = prefix.to_case(Case::Snake);
+        if let Some(rest) = snake.strip_prefix(&prefix).and_then(|r| r.strip_prefix('_')) {
+            if !rest.is_empty() {
+                return rest.to_string();
+            }
+        }
+    }
+    name.to_string()
+}
+
+/// A name made from the method and every segment of the path, e.g. `get_user_by_user_id_account_by_account_id`.
+fn full_path_name(method: &str, path: &str) -> String {
+    let segments = path.split('/').filter(|s| !s.is_empty()).map(|s| {
+        match s.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+            Some(param) => format!("by_{param}"),
+            None => s.to_string(),
+        }
+    });
+    std::iter::once(method.to_string())
+        .chain(segments)
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_yaml::from_str;
+
+    use super::*;
+
+    const SPEC: &str = "openapi: 3.0.0
+info: {title: test, version: '1'}
+paths:
+  /user/{user_id}/account/{account_id}:
+    get: {responses: {'200': {description: ok}}}
+  /user/account/{id}:
+    get: {responses: {'200': {description: ok}}}
+  /users:
+    get: {operationId: api_v1_listUsers, responses: {'200': {description: ok}}}
+    post: {operationId: createUser, x-libninja-name: addUser, responses: {'200': {description: ok}}}";
+
+    fn names(spec: &OpenAPI) -> Vec<&str> {
+        spec.operations()
+            .map(|(_, _, op, _)| op.operation_id.as_deref().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_name_operations() {
+        let mut spec: OpenAPI = from_str(SPEC).unwrap();
+        name_operations(&mut spec, &["ApiV1".to_string()], false).unwrap();
+        assert_eq!(
+            names(&spec),
+            [
+                "get_user_account_by_id",
+                "get_user_account_by_id_2",
+                "list_users",
+                "addUser",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_name_operations_full_path() {
+        let mut spec: OpenAPI = from_str(SPEC).unwrap();
+        spec.paths.paths.swap_indices(0, 1);
+        name_operations(&mut spec, &[], false).unwrap();
+        assert_eq!(names(&spec)[1], "get_user_by_user_id_account_by_account_id");
+        assert_eq!(names(&spec)[2], "api_v1_listUsers");
+    }
+
+    #[test]
+    fn test_name_operations_strict() {
+        let mut spec: OpenAPI = from_str(SPEC).unwrap();
+        let err = name_operations(&mut spec, &[], true).unwrap_err().to_string();
+        assert!(err.contains("GetUserAccountById: GET /user/{user_id}/account/{account_id}, GET /user/account/{id}"));
+    }
+}
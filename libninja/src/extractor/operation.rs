@@ -1,17 +1,21 @@
 use anyhow::{anyhow, Result};
 use convert_case::{Case, Casing};
-use openapiv3::{ArrayType, OpenAPI, Operation, Parameter, PathItem, RefOr, ReferenceOr, Schema, SchemaKind, Type};
+use openapiv3::{
+    ArrayType, OpenAPI, Operation, Parameter, ParameterSchemaOrContent, PathItem, RefOr, ReferenceOr, Schema,
+    SchemaKind, StatusCode, Type,
+};
 use tracing_ez::span;
 
-use hir::{HirSpec, Location};
+use hir::{Constraints, HirSpec, Location, ResponseHeader, RetryOverride};
 use mir::{Doc, DocFormat, Ty};
 
 use crate::extractor;
-use crate::extractor::record::extract_schema;
+use crate::extractor::naming::operation_name;
+use crate::extractor::record::{extract_constraints, extract_schema, is_deprecated};
 use crate::extractor::{is_primitive, schema_ref_to_ty, schema_ref_to_ty2, schema_to_ty};
 
 pub fn extract_operation(spec: &OpenAPI, path: &str, method: &str, op: &Operation, item: &PathItem, hir: &mut HirSpec) {
-    let name = make_name(op.operation_id.as_ref(), method, path);
+    let name = operation_name(op, method, path);
     let doc = extract_doc(op, DocFormat::Markdown);
     let mut parameters = extract_parameters(op, item, spec).unwrap();
     parameters.sort_by(|a, b| a.name.cmp(&b.name));
@@ -19,13 +23,12 @@ pub fn extract_operation(spec: &OpenAPI, path: &str, method: &str, op: &Operatio
         None => Ty::Unit,
         Some(x @ ReferenceOr::Reference { .. }) => schema_ref_to_ty(x, spec),
         Some(ReferenceOr::Item(res)) => {
-            let name = format!("{}Response", name.to_case(Case::Pascal));
-            extract_schema(&name, res, spec, hir);
-            if is_primitive(res, spec) {
-                schema_to_ty(res, spec)
-            } else if matches!(res.kind, SchemaKind::Type(Type::Array(_))) {
+            // Inline array items are named by `hoist_inline_objects`.
+            if is_primitive(res, spec) || matches!(res.kind, SchemaKind::Type(Type::Array(_))) {
                 schema_to_ty(res, spec)
             } else {
+                let name = format!("{}Response", name.to_case(Case::Pascal));
+                extract_schema(&name, res, spec, hir);
                 Ty::Model(name)
             }
         }
@@ -37,11 +40,77 @@ pub fn extract_operation(spec: &OpenAPI, path: &str, method: &str, op: &Operatio
         ret,
         path: path.to_string(),
         method: method.to_string(),
+        deprecated: op.deprecated,
+        retry: extract_retry(op),
+        idempotent: op.extensions.get("x-idempotent").and_then(|v| v.as_bool()),
+        idempotency_key: None,
+        response_headers: extract_response_headers(op, spec),
+        tags: op.tags.clone(),
     });
 }
 
+/// Headers of the 2xx responses, except `Content-Type`, which the spec says to ignore.
+fn extract_response_headers(op: &Operation, spec: &OpenAPI) -> Vec<ResponseHeader> {
+    let mut headers: Vec<ResponseHeader> = Vec::new();
+    for (status, res) in &op.responses.responses {
+        let success = match status {
+            StatusCode::Code(code) => (200..300).contains(code),
+            StatusCode::Range(range) => *range == 2,
+        };
+        if !success {
+            continue;
+        }
+        let Ok(res) = res.resolve(spec) else {
+            continue;
+        };
+        for (name, header) in &res.headers {
+            if name.eq_ignore_ascii_case("content-type") || headers.iter().any(|h| h.name.eq_ignore_ascii_case(name)) {
+                continue;
+            }
+            let header = match header {
+                ReferenceOr::Item(header) => header,
+                ReferenceOr::Reference { reference } => {
+                    let Some(header) = reference
+                        .strip_prefix("#/components/headers/")
+                        .and_then(|name| spec.components.headers.get(name))
+                        .and_then(|header| header.as_item())
+                    else {
+                        continue;
+                    };
+                    header
+                }
+            };
+            let ty = match &header.format {
+                ParameterSchemaOrContent::Schema(schema) => schema_ref_to_ty(schema, spec),
+                ParameterSchemaOrContent::Content(_) => Ty::String,
+            };
+            headers.push(ResponseHeader {
+                name: name.clone(),
+                ty,
+                doc: header.description.clone().map(Doc),
+            });
+        }
+    }
+    headers
+}
+
+/// `x-retry: false`, or `{enabled: true, maxRetries: 5}`
+fn extract_retry(op: &Operation) -> RetryOverride {
+    match op.extensions.get("x-retry") {
+        None => RetryOverride::default(),
+        Some(serde_json::Value::Bool(enabled)) => RetryOverride {
+            enabled: Some(*enabled),
+            max_retries: None,
+        },
+        Some(value) => RetryOverride {
+            enabled: value.get("enabled").and_then(|v| v.as_bool()),
+            max_retries: value.get("maxRetries").and_then(|v| v.as_u64()).map(|n| n as u32),
+        },
+    }
+}
+
 /// make a name for hir::Operation
-fn make_name(operation_id: Option<&String>, method: &str, path: &str) -> String {
+pub(crate) fn make_name(operation_id: Option<&String>, method: &str, path: &str) -> String {
     if let Some(name) = operation_id {
         return name.replace(".", "_");
     }
@@ -64,7 +133,7 @@ fn make_name(operation_id: Option<&String>, method: &str, path: &str) -> String
     format!("{method}{name}{last_group}")
 }
 
-fn extract_doc(operation: &Operation, format: DocFormat) -> Option<Doc> {
+pub fn extract_doc(operation: &Operation, format: DocFormat) -> Option<Doc> {
     let mut doc_pieces = vec![];
     if let Some(summary) = operation.summary.as_ref() {
         if !summary.is_empty() {
@@ -128,14 +197,20 @@ pub fn extract_parameters(op: &Operation, item: &PathItem, spec: &OpenAPI) -> Re
             doc: None,
             location: Location::Body,
             example: body.example.clone(),
+            constraints: extract_constraints(body),
+            default: None,
+            deprecated: false,
         });
         return Ok(inputs);
     }
     let mut props = body.properties_iter(spec).peekable();
 
     if props.peek().is_some() {
+        // Read-only properties are set by the server, so they're never sent.
+        let props = props.filter(|(_, param)| !param.resolve(spec).read_only);
         let body_args = props.map(|(name, param)| {
             let ty = schema_ref_to_ty(param, spec);
+            let deprecated = is_deprecated(param);
             let param: &Schema = param.resolve(spec);
             let optional = extractor::is_optional(name, param, body);
             let name = name.to_string();
@@ -146,6 +221,9 @@ pub fn extract_parameters(op: &Operation, item: &PathItem, spec: &OpenAPI) -> Re
                 doc: None,
                 location: Location::Body,
                 example: body.example.clone(),
+                constraints: extract_constraints(param),
+                default: param.default.clone(),
+                deprecated,
             }
         });
         for param in body_args {
@@ -161,6 +239,9 @@ pub fn extract_parameters(op: &Operation, item: &PathItem, spec: &OpenAPI) -> Re
             doc: None,
             location: Location::Body,
             example: body.example.clone(),
+            constraints: Constraints::default(),
+            default: None,
+            deprecated: false,
         });
     }
     Ok(inputs)
@@ -175,8 +256,6 @@ pub fn get_body<'a>(op: &'a Operation, spec: &'a OpenAPI) -> Option<&'a Schema>
 }
 
 pub fn get_res<'a>(operation: &'a Operation, spec: &'a OpenAPI) -> Option<&'a RefOr<Schema>> {
-    use openapiv3::StatusCode;
-
     let res = &operation.responses.responses;
     let Some(res) = res
         .get(&StatusCode::Code(200))
@@ -209,11 +288,15 @@ pub fn extract_param(param: &ReferenceOr<Parameter>, spec: &OpenAPI) -> Result<h
         location: param.into(),
         ty,
         example: schema.example.clone(),
+        constraints: extract_constraints(schema),
+        default: schema.default.clone(),
+        deprecated: data.deprecated.unwrap_or(false),
     })
 }
 
 #[cfg(test)]
 mod tests {
+    use mir::StringFormat;
     use serde_yaml::from_str;
 
     use super::*;
@@ -244,4 +327,159 @@ mod tests {
         assert_eq!(inputs[8].name, "user_token");
         assert_eq!(inputs[8].optional, true);
     }
+
+    #[test]
+    fn test_extract_retry() {
+        let retry = |yaml: &str| extract_retry(&from_str::<Operation>(yaml).unwrap());
+        assert_eq!(retry("responses: {}"), RetryOverride::default());
+        assert_eq!(retry("x-retry: false\nresponses: {}").enabled, Some(false));
+        let r = retry("x-retry: {enabled: true, maxRetries: 5}\nresponses: {}");
+        assert_eq!(r.enabled, Some(true));
+        assert_eq!(r.max_retries, Some(5));
+    }
+
+    #[test]
+    fn test_idempotency_keys() {
+        let spec: OpenAPI = from_str(
+            "openapi: 3.0.0
+info: {title: test, version: '1'}
+paths:
+  /charges:
+    post:
+      operationId: createCharge
+      responses: {'200': {description: ok}}
+    get:
+      operationId: listCharges
+      x-idempotent: true
+      responses: {'200': {description: ok}}
+  /refunds:
+    post:
+      operationId: createRefund
+      parameters:
+        - {name: idempotency-key, in: header, schema: {type: string}}
+      responses: {'200': {description: ok}}
+    patch:
+      operationId: updateRefund
+      x-idempotent: false
+      responses: {'200': {description: ok}}",
+        )
+        .unwrap();
+        let mut hir = extractor::extract_spec(&spec).unwrap();
+        hir.add_idempotency_keys(Some("Idempotency-Key")).unwrap();
+        let key = |name: &str| hir.get_operation(name).unwrap().idempotency_key.clone();
+        assert_eq!(key("CreateCharge").as_deref(), Some("Idempotency-Key"));
+        assert_eq!(key("ListCharges").as_deref(), Some("Idempotency-Key"));
+        assert_eq!(key("CreateRefund").as_deref(), Some("idempotency-key"));
+        assert_eq!(key("UpdateRefund"), None);
+        assert_eq!(hir.get_operation("CreateRefund").unwrap().parameters.len(), 1);
+        assert!(hir.get_operation("CreateCharge").unwrap().is_idempotent());
+    }
+
+    #[test]
+    fn test_idempotency_key_header_type() {
+        let spec = |schema: &str| {
+            let spec: OpenAPI = from_str(&format!(
+                "openapi: 3.0.0
+info: {{title: test, version: '1'}}
+paths:
+  /refunds:
+    post:
+      operationId: createRefund
+      parameters:
+        - {{name: Idempotency-Key, in: header, schema: {schema}}}
+      responses: {{'200': {{description: ok}}}}"
+            ))
+            .unwrap();
+            extractor::extract_spec(&spec).unwrap()
+        };
+        // A uuid header is a plain string unless the format is kept.
+        let mut hir = spec("{type: string, format: uuid}");
+        hir.retain_string_formats(&[]);
+        hir.add_idempotency_keys(Some("Idempotency-Key")).unwrap();
+        let operation = hir.get_operation("CreateRefund").unwrap();
+        assert_eq!(operation.idempotency_key.as_deref(), Some("Idempotency-Key"));
+        assert_eq!(operation.parameters.len(), 1);
+
+        let mut hir = spec("{type: string, format: uuid}");
+        hir.retain_string_formats(&[StringFormat::Uuid]);
+        let err = hir.add_idempotency_keys(Some("Idempotency-Key")).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("CreateRefund: the Idempotency-Key header can't hold an idempotency key"));
+
+        let mut hir = spec("{type: integer}");
+        assert!(hir.add_idempotency_keys(None).is_ok());
+        assert!(hir.add_idempotency_keys(Some("Idempotency-Key")).is_err());
+    }
+
+    #[test]
+    fn test_extract_response_headers() {
+        let spec: OpenAPI = from_str(
+            "openapi: 3.0.0
+info: {title: test, version: '1'}
+paths:
+  /charges:
+    get:
+      operationId: listCharges
+      responses:
+        '200':
+          description: ok
+          headers:
+            X-RateLimit-Remaining: {description: Requests left, schema: {type: integer}}
+            Content-Type: {schema: {type: string}}
+            Expires: {$ref: '#/components/headers/Expires'}
+        '206':
+          description: partial
+          headers:
+            x-ratelimit-remaining: {schema: {type: integer}}
+            Link: {schema: {type: string}}
+        '400':
+          description: error
+          headers:
+            X-Error-Code: {schema: {type: string}}
+components:
+  headers:
+    Expires: {schema: {type: string, format: date-time}}",
+        )
+        .unwrap();
+        let hir = extractor::extract_spec(&spec).unwrap();
+        let headers = &hir.get_operation("ListCharges").unwrap().response_headers;
+        let names = headers.iter().map(|h| h.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["X-RateLimit-Remaining", "Expires", "Link"]);
+        assert!(matches!(headers[0].ty, Ty::Integer { .. }));
+        assert!(matches!(headers[1].ty, Ty::DateTime));
+        assert_eq!(headers[0].doc.as_ref().map(|d| d.0.as_str()), Some("Requests left"));
+    }
+
+    #[test]
+    fn test_tag_method_names() {
+        let spec: OpenAPI = from_str(
+            "openapi: 3.0.0
+info: {title: test, version: '1'}
+paths:
+  /accounts:
+    get: {operationId: listAccounts, tags: [account], responses: {'200': {description: ok}}}
+    post: {operationId: list, tags: [account], responses: {'200': {description: ok}}}
+  /accounts/{id}:
+    get: {operationId: getAccount, tags: [account, billing], responses: {'200': {description: ok}}}
+  /addresses:
+    get: {operationId: listShippingAddresses, tags: [shipping_address], responses: {'200': {description: ok}}}
+  /policies:
+    get: {operationId: listPolicies, tags: [policy], responses: {'200': {description: ok}}}
+  /ping:
+    get: {operationId: ping, responses: {'200': {description: ok}}}",
+        )
+        .unwrap();
+        let hir = extractor::extract_spec(&spec).unwrap();
+        let name = |name: &str| hir.tag_method_name(hir.get_operation(name).unwrap());
+        assert_eq!(name("ListAccounts"), "list_accounts");
+        assert_eq!(name("List"), "list");
+        assert_eq!(name("GetAccount"), "get");
+        assert_eq!(name("ListShippingAddresses"), "list");
+        assert_eq!(name("ListPolicies"), "list");
+        assert_eq!(name("Ping"), "ping");
+        let tags = hir.operations_by_tag();
+        assert_eq!(tags.keys().copied().collect::<Vec<_>>(), ["account", "policy", "shipping_address"]);
+        assert_eq!(tags["account"].len(), 3);
+    }
 }
@@ -1,13 +1,14 @@
-use std::collections::{BTreeMap, HashSet};
+use std::collections::{BTreeMap, HashSet, VecDeque};
 
 use convert_case::{Case, Casing};
 /// Records are the "model"s of the MIR world. model is a crazy overloaded word though.
 use openapiv3::{
-    AdditionalProperties, ObjectType, OpenAPI, RefOrMap, ReferenceOr, Schema, SchemaData, SchemaKind, SchemaReference,
-    StringType, Type,
+    AdditionalProperties, ArrayType, IntegerType, NumberType, ObjectType, OpenAPI, RefOrMap, ReferenceOr, Schema,
+    SchemaData, SchemaKind, SchemaReference, StringType, Type,
 };
+use tracing::{debug, warn};
 
-use hir::{Enum, HirField, HirSpec, NewType, Record, Struct, TypeAlias, Variant};
+use hir::{Constraints, Enum, HirField, HirSpec, NewType, Record, Struct, Variant};
 use mir::{Doc, Ty};
 
 use crate::{
@@ -31,12 +32,32 @@ fn extract_fields(properties: &RefOrMap<Schema>, parent: &Schema, spec: &OpenAPI
                     doc: extract_docs(schema),
                     example: schema.example.clone(),
                     flatten: false,
+                    constraints: extract_constraints(schema),
+                    read_only: schema.read_only,
+                    write_only: schema.write_only,
+                    default: schema.default.clone(),
+                    boxed: false,
+                    deprecated: is_deprecated(schema_ref),
                 },
             )
         })
         .collect()
 }
 
+/// A flattened map collecting the keys that aren't listed in `properties`.
+fn additional_properties_field(additional: Option<&AdditionalProperties>, spec: &OpenAPI) -> Option<HirField> {
+    let value = match additional? {
+        AdditionalProperties::Any(true) => Ty::default(),
+        AdditionalProperties::Any(false) => return None,
+        AdditionalProperties::Schema(s) => schema_ref_to_ty(s, spec),
+    };
+    Some(HirField {
+        doc: Some(Doc("Properties not listed in the API spec.".to_string())),
+        flatten: true,
+        ..HirField::new(Ty::HashMap(Box::new(value)))
+    })
+}
+
 fn create_field(field_schema_ref: &ReferenceOr<Schema>, spec: &OpenAPI) -> HirField {
     let field_schema = field_schema_ref.resolve(spec);
     let ty = schema_ref_to_ty2(field_schema_ref, spec, field_schema);
@@ -49,6 +70,12 @@ fn create_field(field_schema_ref: &ReferenceOr<Schema>, spec: &OpenAPI) -> HirFi
         doc,
         example,
         flatten: false,
+        constraints: extract_constraints(field_schema),
+        read_only: field_schema.read_only,
+        write_only: field_schema.write_only,
+        default: field_schema.default.clone(),
+        boxed: false,
+        deprecated: is_deprecated(field_schema_ref),
     }
 }
 
@@ -66,7 +93,7 @@ pub fn effective_length(all_of: &[ReferenceOr<Schema>]) -> usize {
 }
 
 pub fn extract_schema(name: &str, schema: &Schema, spec: &OpenAPI, hir: &mut HirSpec) {
-    println!("Extracting schema: {}", name);
+    debug!("Extracting schema: {}", name);
     let name = name.to_string();
 
     let k = &schema.kind;
@@ -87,43 +114,83 @@ pub fn extract_schema(name: &str, schema: &Schema, spec: &OpenAPI, hir: &mut Hir
                 }
             };
             let ty = Ty::HashMap(Box::new(ty));
-            let t = TypeAlias {
-                name: name.clone(),
-                ty,
-                optional: false,
+            let field = HirField {
+                deprecated: schema.deprecated,
+                ..HirField::new(ty)
             };
-            hir.insert_schema(t);
+            hir.insert_schema(Record::TypeAlias(name.clone(), field));
             return;
         } else {
-            let fields = extract_fields(properties, schema, spec);
+            let mut fields = extract_fields(properties, schema, spec);
+            if let Some(extra) = additional_properties_field(additional_properties.as_ref(), spec) {
+                match ["extra", "additional_properties"]
+                    .into_iter()
+                    .find(|n| !fields.contains_key(*n))
+                {
+                    Some(field) => {
+                        fields.insert(field.to_string(), extra);
+                    }
+                    None => warn!("No free field name for additionalProperties of {}", name),
+                }
+            }
             let s = Struct {
                 name: name.clone(),
                 fields,
                 nullable: schema.nullable,
                 docs: schema.description.as_ref().map(|d| Doc(d.trim().to_string())),
+                deprecated: schema.deprecated,
+                example: schema.example.clone(),
             };
             hir.insert_schema(s);
             return;
         }
     } else if let SchemaKind::Type(Type::String(StringType { enumeration, .. })) = k {
         let lookup = schema.extensions.get("x-rename").and_then(|v| v.as_object());
+        let names = enum_variant_names(schema);
         if !enumeration.is_empty() {
             let s = Enum {
                 name: name.clone(),
                 variants: enumeration
                     .iter()
-                    .map(|s| {
+                    .enumerate()
+                    .map(|(i, s)| {
                         let alias = lookup
                             .and_then(|l| l.get(s))
                             .and_then(|v| v.as_str())
+                            .or_else(|| names.get(i).copied())
                             .map(|s| s.to_string());
                         Variant {
                             value: s.clone(),
                             alias,
+                            discriminant: None,
                         }
                     })
                     .collect(),
                 doc: schema.description.as_ref().map(|d| Doc(d.clone())),
+                open: enum_open(schema),
+                deprecated: schema.deprecated,
+            };
+            hir.insert_schema(s);
+            return;
+        }
+    } else if let SchemaKind::Type(Type::Integer(IntegerType { enumeration, .. })) = k {
+        let names = enum_variant_names(schema);
+        if !enumeration.is_empty() {
+            let s = Enum {
+                name: name.clone(),
+                variants: enumeration
+                    .iter()
+                    .flatten()
+                    .enumerate()
+                    .map(|(i, n)| Variant {
+                        value: n.to_string(),
+                        alias: names.get(i).map(|s| s.to_string()),
+                        discriminant: Some(*n),
+                    })
+                    .collect(),
+                doc: schema.description.as_ref().map(|d| Doc(d.clone())),
+                open: enum_open(schema),
+                deprecated: schema.deprecated,
             };
             hir.insert_schema(s);
             return;
@@ -161,8 +228,15 @@ fn extract_newtype(name: String, schema: &Schema, spec: &OpenAPI, hir: &mut HirS
             doc: None,
             example: None,
             flatten: false,
+            constraints: Constraints::default(),
+            read_only: false,
+            write_only: false,
+            default: None,
+            boxed: false,
+            deprecated: false,
         }],
         doc: schema.description.as_ref().map(|d| Doc(d.clone())),
+        deprecated: schema.deprecated,
     };
     hir.insert_schema(t);
 }
@@ -173,6 +247,7 @@ fn extract_all_of(name: String, all_of: &[ReferenceOr<Schema>], data: &SchemaDat
         let field = HirField {
             ty,
             optional: data.nullable,
+            deprecated: data.deprecated,
             ..HirField::default()
         };
         hir.insert_schema(Record::TypeAlias(name.clone(), field));
@@ -207,6 +282,8 @@ fn extract_all_of(name: String, all_of: &[ReferenceOr<Schema>], data: &SchemaDat
         name: name.to_string(),
         fields,
         docs: data.description.as_ref().map(|d| Doc(d.clone())),
+        deprecated: data.deprecated,
+        example: data.example.clone(),
     };
     hir.insert_schema(s);
 }
@@ -236,10 +313,201 @@ fn create_unique_name(current_schemas: &HashSet<String>, name: &str, field: &str
     None
 }
 
+/// Give every inline object schema (left as `Ty::Any` by `schema_to_ty`) a named record, in operation
+/// parameters and responses, record fields, array items and map values. Names come from the parent and the
+/// field, e.g. `UserAddress` for `User.address`, and hoisted records are searched in turn.
+pub fn hoist_inline_objects(spec: &OpenAPI, hir: &mut HirSpec) {
+    let mut hoisted = Vec::new();
+    let mut operations = std::mem::take(&mut hir.operations);
+    for operation in &mut operations {
+        for param in &mut operation.parameters {
+            hoist_inline_object(&mut param.ty, &operation.name, &param.name, spec, hir, &mut hoisted);
+        }
+        hoist_inline_object(&mut operation.ret, &operation.name, "Response", spec, hir, &mut hoisted);
+    }
+    hir.operations = operations;
+    let mut webhooks = std::mem::take(&mut hir.webhooks);
+    for webhook in &mut webhooks {
+        hoist_inline_object(&mut webhook.body, &webhook.name, "Webhook", spec, hir, &mut hoisted);
+    }
+    hir.webhooks = webhooks;
+
+    let mut queue = hir.schemas.keys().cloned().collect::<VecDeque<_>>();
+    while let Some(name) = queue.pop_front() {
+        let mut record = hir.schemas[&name].clone();
+        let mut hoisted = Vec::new();
+        match &mut record {
+            Record::Struct(s) => {
+                for (field, f) in &mut s.fields {
+                    hoist_inline_object(&mut f.ty, &name, field, spec, hir, &mut hoisted);
+                }
+            }
+            record => {
+                for f in record.fields_mut() {
+                    hoist_inline_object(&mut f.ty, &name, "", spec, hir, &mut hoisted);
+                }
+            }
+        }
+        hir.schemas.insert(name, record);
+        queue.extend(hoisted);
+    }
+}
+
+/// Array items are named after the singular field (or `{Field}Item`), and map values `{Field}Value`.
+fn hoist_inline_object(
+    ty: &mut Ty,
+    parent: &str,
+    field: &str,
+    spec: &OpenAPI,
+    hir: &mut HirSpec,
+    hoisted: &mut Vec<String>,
+) {
+    match ty {
+        Ty::Array(inner) => {
+            let item = if !field.is_empty() && is_plural(field) {
+                singular(field).to_string()
+            } else {
+                format!("{field}Item")
+            };
+            hoist_inline_object(inner, parent, &item, spec, hir, hoisted);
+        }
+        Ty::HashMap(inner) => hoist_inline_object(inner, parent, &format!("{field}Value"), spec, hir, hoisted),
+        Ty::Any(Some(schema)) => {
+            let SchemaKind::Type(Type::Object(obj)) = &schema.kind else {
+                return;
+            };
+            if !obj.properties.is_empty() {
+                let name = unique_name(
+                    hir,
+                    &format!("{}{}", parent.to_case(Case::Pascal), field.to_case(Case::Pascal)),
+                );
+                extract_schema(&name, schema, spec, hir);
+                *ty = Ty::Model(name.clone());
+                hoisted.push(name);
+            } else if let Some(AdditionalProperties::Schema(value)) = &obj.additional_properties {
+                let mut inner = schema_ref_to_ty(value, spec);
+                hoist_inline_object(&mut inner, parent, &format!("{field}Value"), spec, hir, hoisted);
+                *ty = Ty::HashMap(Box::new(inner));
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Collisions get a numeric suffix, e.g. `UserAddress2`.
+fn unique_name(hir: &HirSpec, name: &str) -> String {
+    if !hir.schemas.contains_key(name) {
+        return name.to_string();
+    }
+    (2..)
+        .map(|i| format!("{name}{i}"))
+        .find(|n| !hir.schemas.contains_key(n))
+        .unwrap()
+}
+
+/// Only inline schemas count, since a deprecated `$ref` target marks its own record.
+pub fn is_deprecated(schema_ref: &ReferenceOr<Schema>) -> bool {
+    schema_ref.as_item().is_some_and(|s| s.deprecated)
+}
+
 pub fn extract_docs(schema: &Schema) -> Option<Doc> {
     schema.description.as_ref().map(|d| Doc(d.trim().to_string()))
 }
 
+/// Variant names from `x-enum-varnames` (openapi-generator) or `x-enumNames` (NSwag), in the same order as `enum`.
+fn enum_variant_names(schema: &Schema) -> Vec<&str> {
+    schema
+        .extensions
+        .get("x-enum-varnames")
+        .or_else(|| schema.extensions.get("x-enumNames"))
+        .and_then(|v| v.as_array())
+        .map(|names| names.iter().filter_map(|n| n.as_str()).collect())
+        .unwrap_or_default()
+}
+
+fn enum_open(schema: &Schema) -> Option<bool> {
+    schema
+        .extensions
+        .get("x-enum-closed")
+        .and_then(|v| v.as_bool())
+        .map(|closed| !closed)
+}
+
+pub fn extract_constraints(schema: &Schema) -> Constraints {
+    match &schema.kind {
+        SchemaKind::Type(Type::String(StringType {
+            pattern,
+            min_length,
+            max_length,
+            ..
+        })) => {
+            // Some specs write patterns as JS regex literals, e.g. `/^[a-z]+$/`.
+            let pattern = pattern.as_ref().map(|p| {
+                p.strip_prefix('/')
+                    .and_then(|p| p.strip_suffix('/'))
+                    .unwrap_or(p)
+                    .to_string()
+            });
+            // ECMA regexes can use features (e.g. lookaround) the regex crate doesn't support.
+            let pattern = pattern.filter(|p| {
+                let valid = regex::Regex::new(p).is_ok();
+                if !valid {
+                    warn!("Skipping validation for unsupported pattern: {}", p);
+                }
+                valid
+            });
+            Constraints {
+                min_length: *min_length,
+                max_length: *max_length,
+                pattern,
+                ..Constraints::default()
+            }
+        }
+        SchemaKind::Type(Type::Integer(IntegerType {
+            multiple_of,
+            exclusive_minimum,
+            exclusive_maximum,
+            minimum,
+            maximum,
+            ..
+        })) => Constraints {
+            minimum: minimum.map(|n| n as f64),
+            maximum: maximum.map(|n| n as f64),
+            exclusive_minimum: *exclusive_minimum,
+            exclusive_maximum: *exclusive_maximum,
+            multiple_of: multiple_of.map(|n| n as f64),
+            ..Constraints::default()
+        },
+        SchemaKind::Type(Type::Number(NumberType {
+            multiple_of,
+            exclusive_minimum,
+            exclusive_maximum,
+            minimum,
+            maximum,
+            ..
+        })) => Constraints {
+            minimum: *minimum,
+            maximum: *maximum,
+            exclusive_minimum: *exclusive_minimum,
+            exclusive_maximum: *exclusive_maximum,
+            multiple_of: *multiple_of,
+            ..Constraints::default()
+        },
+        SchemaKind::Type(Type::Array(ArrayType {
+            min_items,
+            max_items,
+            unique_items,
+            ..
+        })) => Constraints {
+            min_items: *min_items,
+            max_items: *max_items,
+            unique_items: *unique_items,
+            ..Constraints::default()
+        },
+        _ => Constraints::default(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use openapiv3::{OpenAPI, Schema, SchemaData, SchemaKind};
@@ -266,4 +534,247 @@ mod tests {
         assert_eq!(eye_color.optional, false);
         assert_eq!(weight.optional, true);
     }
+
+    #[test]
+    fn test_extract_constraints() {
+        let schema: Schema = from_str("type: string\nmaxLength: 20\npattern: /^[a-z0-9_+-]+$/").unwrap();
+        let c = extract_constraints(&schema);
+        assert_eq!(c.max_length, Some(20));
+        assert_eq!(c.pattern.as_deref(), Some("^[a-z0-9_+-]+$"));
+
+        let schema: Schema = from_str("type: string\npattern: ^(?!foo)").unwrap();
+        assert!(extract_constraints(&schema).is_empty());
+
+        let schema: Schema = from_str("type: integer\nminimum: 1\nexclusiveMaximum: true\nmaximum: 100").unwrap();
+        let c = extract_constraints(&schema);
+        assert_eq!(c.minimum, Some(1.0));
+        assert_eq!(c.maximum, Some(100.0));
+        assert!(c.exclusive_maximum);
+    }
+
+    #[test]
+    fn test_open_enums() {
+        let mut hir = HirSpec::default();
+        let spec: OpenAPI = from_str(
+            "openapi: 3.0.0
+info: {title: test, version: '1'}
+paths: {}
+components:
+  schemas:
+    Status: {type: string, enum: [active, closed]}
+    Kind: {type: string, enum: [a, b], x-enum-closed: true}
+    Filter: {type: string, enum: [all, some]}
+    Account:
+      type: object
+      properties:
+        status: {$ref: '#/components/schemas/Status'}",
+        )
+        .unwrap();
+        for (name, schema) in &spec.components.schemas {
+            extract_schema(name, schema.as_item().unwrap(), &spec, &mut hir);
+        }
+        hir.operations.push(hir::Operation {
+            ret: Ty::Model("Account".to_string()),
+            ..hir::Operation::default()
+        });
+        hir.resolve_open_enums(hir::OpenEnums::Responses);
+        let open = |name: &str| match hir.get_record(name).unwrap() {
+            Record::Enum(e) => e.open,
+            _ => panic!(),
+        };
+        assert_eq!(open("Status"), Some(true));
+        assert_eq!(open("Kind"), Some(false));
+        assert_eq!(open("Filter"), Some(false));
+    }
+
+    #[test]
+    fn test_integer_enum() {
+        let mut hir = HirSpec::default();
+        let spec = OpenAPI::default();
+        let schema: Schema = from_str("type: integer\nenum: [0, -1, 2]\nx-enumNames: [Pending, Failed]").unwrap();
+        extract_schema("Status", &schema, &spec, &mut hir);
+        let Record::Enum(e) = hir.get_record("Status").unwrap() else {
+            panic!()
+        };
+        assert!(e.is_integer());
+        let names = e.iter_safe_variant_names().map(|(n, _)| n).collect::<Vec<_>>();
+        assert_eq!(names, vec!["Pending", "Failed", "Status2"]);
+        assert_eq!(e.variants[1].discriminant, Some(-1));
+    }
+
+    #[test]
+    fn test_split_read_write() {
+        let spec: OpenAPI = from_str(
+            "openapi: 3.0.0
+info: {title: test, version: '1'}
+paths:
+  /users:
+    post:
+      operationId: createUser
+      requestBody:
+        content:
+          application/json:
+            schema:
+              type: object
+              properties:
+                user: {$ref: '#/components/schemas/User'}
+      responses:
+        '200':
+          description: ok
+          content:
+            application/json:
+              schema: {$ref: '#/components/schemas/User'}
+components:
+  schemas:
+    User:
+      type: object
+      properties:
+        id: {type: string, readOnly: true}
+        password: {type: string, writeOnly: true}",
+        )
+        .unwrap();
+        let mut hir = extractor::extract_spec(&spec).unwrap();
+        hir.split_read_write();
+        let fields = |name: &str| {
+            let s = hir.get_record(name).unwrap().as_struct().unwrap();
+            s.fields.keys().cloned().collect::<Vec<_>>()
+        };
+        assert_eq!(fields("User"), vec!["id"]);
+        assert_eq!(fields("UserCreate"), vec!["password"]);
+        assert_eq!(hir.operations[0].parameters[0].ty.inner_model().unwrap(), "UserCreate");
+    }
+
+    #[test]
+    fn test_mark_recursive_fields() {
+        let mut hir = HirSpec::default();
+        let spec: OpenAPI = from_str(
+            "openapi: 3.0.0
+info: {title: test, version: '1'}
+paths: {}
+components:
+  schemas:
+    Comment:
+      type: object
+      properties:
+        parent: {$ref: '#/components/schemas/Comment'}
+        replies: {type: array, items: {$ref: '#/components/schemas/Comment'}}
+    Edge:
+      type: object
+      properties:
+        target: {$ref: '#/components/schemas/Node'}
+    Node:
+      type: object
+      properties:
+        edge: {$ref: '#/components/schemas/Edge'}
+        name: {type: string}",
+        )
+        .unwrap();
+        for (name, schema) in &spec.components.schemas {
+            extract_schema(name, schema.as_item().unwrap(), &spec, &mut hir);
+        }
+        hir.mark_recursive_fields();
+        let boxed = |name: &str| {
+            let s = hir.get_record(name).unwrap().as_struct().unwrap();
+            s.fields.iter().filter(|(_, f)| f.boxed).map(|(n, _)| n.clone()).collect::<Vec<_>>()
+        };
+        assert_eq!(boxed("Comment"), vec!["parent"]);
+        // The search starts from `Edge`, so the edge back to it is the one boxed.
+        assert!(boxed("Edge").is_empty());
+        assert_eq!(boxed("Node"), vec!["edge"]);
+    }
+
+    #[test]
+    fn test_hoist_inline_objects() {
+        let spec: OpenAPI = from_str(
+            "openapi: 3.0.0
+info: {title: test, version: '1'}
+paths: {}
+components:
+  schemas:
+    User:
+      type: object
+      properties:
+        address:
+          type: object
+          properties:
+            geo: {type: object, properties: {lat: {type: number}}}
+        pets: {type: array, items: {type: object, properties: {name: {type: string}}}}
+        labels: {type: object, additionalProperties: {type: object, properties: {color: {type: string}}}}
+    UserAddress: {type: object, properties: {line: {type: string}}}",
+        )
+        .unwrap();
+        let hir = extractor::extract_without_treeshake(&spec).unwrap();
+        let ty = |name: &str, field: &str| {
+            let s = hir.get_record(name).unwrap().as_struct().unwrap();
+            s.fields[field].ty.clone()
+        };
+        assert_eq!(ty("User", "address").inner_model().unwrap(), "UserAddress2");
+        assert_eq!(ty("UserAddress2", "geo").inner_model().unwrap(), "UserAddress2Geo");
+        assert!(matches!(ty("User", "pets"), Ty::Array(_)));
+        assert_eq!(ty("User", "pets").inner_model().unwrap(), "UserPet");
+        let Ty::HashMap(value) = ty("User", "labels") else {
+            panic!()
+        };
+        assert!(matches!(*value, Ty::Model(ref m) if m == "UserLabelsValue"));
+    }
+
+    #[test]
+    fn test_additional_properties_with_properties() {
+        let mut hir = HirSpec::default();
+        let spec = OpenAPI::default();
+        let schema: Schema = from_str(
+            "type: object
+properties:
+  id: {type: string}
+additionalProperties: {type: integer}",
+        )
+        .unwrap();
+        extract_schema("Meta", &schema, &spec, &mut hir);
+        let s = hir.get_record("Meta").unwrap().as_struct().unwrap();
+        let extra = &s.fields["extra"];
+        assert!(extra.flatten);
+        assert!(matches!(&extra.ty, Ty::HashMap(v) if matches!(**v, Ty::Integer { .. })));
+
+        let schema: Schema =
+            from_str("type: object\nproperties:\n  id: {type: string}\nadditionalProperties: false").unwrap();
+        extract_schema("Closed", &schema, &spec, &mut hir);
+        assert_eq!(hir.get_record("Closed").unwrap().len_fields(), 1);
+    }
+
+    #[test]
+    fn test_deprecated() {
+        let spec: OpenAPI = from_str(
+            "openapi: 3.0.0
+info: {title: test, version: '1'}
+paths:
+  /things:
+    get:
+      operationId: listThings
+      deprecated: true
+      parameters:
+        - {name: q, in: query, deprecated: true, schema: {type: string}}
+      responses:
+        '200':
+          description: ok
+          content:
+            application/json:
+              schema: {$ref: '#/components/schemas/Thing'}
+components:
+  schemas:
+    Legacy: {type: object, deprecated: true, properties: {id: {type: string}}}
+    Thing:
+      type: object
+      properties:
+        old_name: {type: string, deprecated: true}
+        legacy: {$ref: '#/components/schemas/Legacy'}",
+        )
+        .unwrap();
+        let hir = extractor::extract_spec(&spec).unwrap();
+        assert!(hir.operations[0].deprecated);
+        assert!(hir.operations[0].parameters[0].deprecated);
+        assert!(hir.get_record("Legacy").unwrap().deprecated());
+        let thing = hir.get_record("Thing").unwrap().as_struct().unwrap();
+        assert!(thing.fields["old_name"].deprecated);
+        assert!(!thing.fields["legacy"].deprecated);
+    }
 }
@@ -1,5 +1,8 @@
 use openapiv3 as oa;
-use openapiv3::{ArrayType, OpenAPI, RefOr, ReferenceOr, Schema, SchemaKind, SchemaReference};
+use openapiv3::{
+    ArrayType, IntegerType, NumberType, OpenAPI, RefOr, ReferenceOr, Schema, SchemaKind, SchemaReference,
+    VariantOrUnknownOrEmpty,
+};
 use tracing::warn;
 
 use mir::Ty;
@@ -36,20 +39,24 @@ pub fn schema_to_ty(schema: &Schema, spec: &OpenAPI) -> Ty {
             },
             "integer" => Ty::Integer {
                 ser: mir::IntegerSerialization::String,
+                size: mir::IntegerSize::I64,
             },
             "date" => Ty::Date {
                 ser: mir::DateSerialization::Iso8601,
             },
             "date-time" => Ty::DateTime,
-            _ => Ty::String,
+            format => mir::StringFormat::from_format(format)
+                .map(Ty::FormattedString)
+                .unwrap_or(Ty::String),
         },
-        SchemaKind::Type(oa::Type::Number(_)) => Ty::Float,
-        SchemaKind::Type(oa::Type::Integer(_)) => {
+        SchemaKind::Type(oa::Type::Number(n)) => Ty::Float { size: float_size(n) },
+        SchemaKind::Type(oa::Type::Integer(int)) => {
             let ext = &schema.data.extensions;
             let null_as_zero = ext.get("x-null-as-zero").and_then(|v| v.as_bool()).unwrap_or(false);
             if null_as_zero {
                 return Ty::Integer {
                     ser: mir::IntegerSerialization::NullAsZero,
+                    size: mir::IntegerSize::I64,
                 };
             }
             match schema.data.extensions.get("x-format").and_then(|s| s.as_str()) {
@@ -58,6 +65,7 @@ pub fn schema_to_ty(schema: &Schema, spec: &OpenAPI) -> Ty {
                 },
                 _ => Ty::Integer {
                     ser: mir::IntegerSerialization::Simple,
+                    size: integer_size(int),
                 },
             }
         }
@@ -85,6 +93,31 @@ pub fn schema_to_ty(schema: &Schema, spec: &OpenAPI) -> Ty {
     }
 }
 
+/// Width comes from `format` (int8 through int64, uint8 through uint64). Integers are
+/// unsigned when the format says so, or when the schema declares `minimum >= 0`.
+fn integer_size(int: &IntegerType) -> mir::IntegerSize {
+    let format = match &int.format {
+        VariantOrUnknownOrEmpty::Item(oa::IntegerFormat::Int32) => "int32",
+        VariantOrUnknownOrEmpty::Item(oa::IntegerFormat::Int64) => "int64",
+        VariantOrUnknownOrEmpty::Unknown(s) => s.as_str(),
+        VariantOrUnknownOrEmpty::Empty => "",
+    };
+    let unsigned = format.starts_with("uint") || int.minimum.is_some_and(|m| m >= 0);
+    let bits = format
+        .trim_start_matches('u')
+        .trim_start_matches("int")
+        .parse()
+        .unwrap_or(64);
+    mir::IntegerSize::new(bits, !unsigned)
+}
+
+fn float_size(n: &NumberType) -> mir::FloatSize {
+    match &n.format {
+        VariantOrUnknownOrEmpty::Item(oa::NumberFormat::Float) => mir::FloatSize::F32,
+        _ => mir::FloatSize::F64,
+    }
+}
+
 /// what exactly is this?
 pub fn is_primitive(schema: &Schema, spec: &OpenAPI) -> bool {
     use openapiv3::SchemaKind::*;
@@ -92,7 +125,7 @@ pub fn is_primitive(schema: &Schema, spec: &OpenAPI) -> bool {
     match &schema.kind {
         Type(String(s)) => s.enumeration.is_empty(),
         Type(Number(_)) => true,
-        Type(Integer(_)) => true,
+        Type(Integer(i)) => i.enumeration.is_empty(),
         Type(Boolean {}) => true,
         Type(Array(ArrayType { items: Some(inner), .. })) => {
             let inner = inner.resolve(spec);
@@ -102,3 +135,46 @@ pub fn is_primitive(schema: &Schema, spec: &OpenAPI) -> bool {
         _ => false,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use mir::{FloatSize, IntegerSize, StringFormat};
+    use serde_yaml::from_str;
+
+    use super::*;
+
+    fn ty(yaml: &str) -> Ty {
+        let schema: Schema = from_str(yaml).unwrap();
+        schema_to_ty(&schema, &OpenAPI::default())
+    }
+
+    #[test]
+    fn test_integer_size_from_format() {
+        assert!(matches!(ty("type: integer"), Ty::Integer { size: IntegerSize::I64, .. }));
+        assert!(matches!(ty("{type: integer, format: int32}"), Ty::Integer { size: IntegerSize::I32, .. }));
+        assert!(matches!(ty("{type: integer, format: uint64}"), Ty::Integer { size: IntegerSize::U64, .. }));
+        assert!(matches!(
+            ty("{type: integer, format: int32, minimum: 0}"),
+            Ty::Integer { size: IntegerSize::U32, .. }
+        ));
+        assert!(matches!(
+            ty("{type: integer, minimum: -1}"),
+            Ty::Integer { size: IntegerSize::I64, .. }
+        ));
+    }
+
+    #[test]
+    fn test_float_size_from_format() {
+        assert!(matches!(ty("{type: number, format: float}"), Ty::Float { size: FloatSize::F32 }));
+        assert!(matches!(ty("{type: number, format: double}"), Ty::Float { size: FloatSize::F64 }));
+        assert!(matches!(ty("type: number"), Ty::Float { size: FloatSize::F64 }));
+    }
+
+    #[test]
+    fn test_string_format() {
+        assert!(matches!(ty("{type: string, format: uuid}"), Ty::FormattedString(StringFormat::Uuid)));
+        assert!(matches!(ty("{type: string, format: ipv4}"), Ty::FormattedString(StringFormat::Ip)));
+        assert!(matches!(ty("{type: string, format: byte}"), Ty::FormattedString(StringFormat::Byte)));
+        assert!(matches!(ty("{type: string, format: password}"), Ty::String));
+    }
+}
@@ -0,0 +1,237 @@
+use convert_case::{Case, Casing};
+use indexmap::IndexMap;
+use openapiv3::{Callback, OpenAPI, Operation, PathItem, ReferenceOr};
+use serde_json::Value;
+use tracing::warn;
+
+use hir::{HirSpec, HmacAlgorithm, SignatureEncoding, Webhook, WebhookEvent, WebhookSignature};
+use mir::{DocFormat, Ty};
+
+use crate::extractor::operation::extract_doc;
+use crate::extractor::{schema_ref_to_ty, schema_to_ty};
+
+/// `openapiv3` drops the OpenAPI 3.1 `webhooks` object and operation `callbacks`. Move them to the `x-webhooks`
+/// and `x-callbacks` extensions of the raw spec, so they survive parsing.
+pub fn preserve_webhooks(spec: &mut Value) {
+    let Some(spec) = spec.as_object_mut() else {
+        return;
+    };
+    if let Some(webhooks) = spec.remove("webhooks") {
+        spec.insert("x-webhooks".to_string(), webhooks);
+    }
+    let Some(paths) = spec.get_mut("paths").and_then(|p| p.as_object_mut()) else {
+        return;
+    };
+    for item in paths.values_mut().filter_map(|i| i.as_object_mut()) {
+        for operation in item.values_mut().filter_map(|o| o.as_object_mut()) {
+            if let Some(callbacks) = operation.remove("callbacks") {
+                operation.insert("x-callbacks".to_string(), callbacks);
+            }
+        }
+    }
+}
+
+pub fn extract_webhooks(spec: &OpenAPI, hir: &mut HirSpec) {
+    if let Some(webhooks) = spec.extensions.get("x-webhooks") {
+        match serde_json::from_value::<IndexMap<String, ReferenceOr<PathItem>>>(webhooks.clone()) {
+            Ok(webhooks) => {
+                for (name, item) in &webhooks {
+                    let Some(item) = item.as_item() else {
+                        warn!("Webhook {name} is a reference, which isn't supported.");
+                        continue;
+                    };
+                    for (_, operation) in item.iter() {
+                        hir.webhooks.push(extract_webhook(name, operation, spec));
+                    }
+                }
+            }
+            Err(e) => warn!("Failed to parse webhooks: {e}"),
+        }
+    }
+    for (_, _, operation, _) in spec.operations() {
+        let Some(callbacks) = operation.extensions.get("x-callbacks") else {
+            continue;
+        };
+        let callbacks = match serde_json::from_value::<IndexMap<String, ReferenceOr<Callback>>>(callbacks.clone()) {
+            Ok(callbacks) => callbacks,
+            Err(e) => {
+                warn!("Failed to parse callbacks: {e}");
+                continue;
+            }
+        };
+        for (name, callback) in &callbacks {
+            let Some(callback) = resolve_callback(callback, spec) else {
+                warn!("Callback {name} not found.");
+                continue;
+            };
+            for item in callback.values() {
+                for (_, operation) in item.iter() {
+                    let name = operation.operation_id.as_ref().unwrap_or(name);
+                    if hir.webhooks.iter().any(|w| w.name == name.to_case(Case::Pascal)) {
+                        continue;
+                    }
+                    hir.webhooks.push(extract_webhook(name, operation, spec));
+                }
+            }
+        }
+    }
+    hir.webhook_event = spec.extensions.get("x-webhook-event").and_then(extract_event);
+    hir.webhook_signature = spec.extensions.get("x-webhook-signature").and_then(extract_signature);
+}
+
+fn resolve_callback<'a>(callback: &'a ReferenceOr<Callback>, spec: &'a OpenAPI) -> Option<&'a Callback> {
+    match callback {
+        ReferenceOr::Item(callback) => Some(callback),
+        ReferenceOr::Reference { reference } => {
+            let name = reference.strip_prefix("#/components/callbacks/")?;
+            spec.components.callbacks.get(name)?.as_item()
+        }
+    }
+}
+
+/// The event defaults to the key in `webhooks` or `callbacks`, and can be set with `x-event-name`.
+fn extract_webhook(name: &str, operation: &Operation, spec: &OpenAPI) -> Webhook {
+    let body = operation
+        .request_body
+        .as_ref()
+        .and_then(|b| b.resolve(spec).ok())
+        .and_then(|b| b.content.get("application/json"))
+        .and_then(|media| media.schema.as_ref());
+    // Inline objects are named by `hoist_inline_objects`.
+    let body = match body {
+        None => Ty::Unit,
+        Some(s @ ReferenceOr::Reference { .. }) => schema_ref_to_ty(s, spec),
+        Some(ReferenceOr::Item(s)) => schema_to_ty(s, spec),
+    };
+    let event = operation
+        .extensions
+        .get("x-event-name")
+        .and_then(|v| v.as_str())
+        .unwrap_or(name);
+    Webhook {
+        name: name.to_case(Case::Pascal),
+        event: event.to_string(),
+        doc: extract_doc(operation, DocFormat::Markdown),
+        body,
+        deprecated: operation.deprecated,
+    }
+}
+
+/// `x-webhook-event: {header: X-Event-Type}` or `{field: type}`
+fn extract_event(value: &Value) -> Option<WebhookEvent> {
+    let str = |key: &str| value.get(key).and_then(|v| v.as_str()).map(|s| s.to_string());
+    match (str("header"), str("field")) {
+        (Some(header), _) => Some(WebhookEvent::Header(header)),
+        (_, Some(field)) => Some(WebhookEvent::Field(field)),
+        _ => {
+            warn!("x-webhook-event needs a `header` or `field`: {value}");
+            None
+        }
+    }
+}
+
+/// `x-webhook-signature: {header: X-Signature, algorithm: sha256, encoding: hex, prefix: "sha256="}`
+fn extract_signature(value: &Value) -> Option<WebhookSignature> {
+    let str = |key: &str| value.get(key).and_then(|v| v.as_str());
+    let Some(header) = str("header") else {
+        warn!("x-webhook-signature needs a `header`: {value}");
+        return None;
+    };
+    let algorithm = match str("algorithm") {
+        None | Some("sha256") => HmacAlgorithm::Sha256,
+        Some("sha1") => HmacAlgorithm::Sha1,
+        Some("sha512") => HmacAlgorithm::Sha512,
+        Some(a) => {
+            warn!("Unknown x-webhook-signature algorithm {a}. Expected sha1, sha256 or sha512.");
+            return None;
+        }
+    };
+    let encoding = match str("encoding") {
+        None | Some("hex") => SignatureEncoding::Hex,
+        Some("base64") => SignatureEncoding::Base64,
+        Some(e) => {
+            warn!("Unknown x-webhook-signature encoding {e}. Expected hex or base64.");
+            return None;
+        }
+    };
+    Some(WebhookSignature {
+        header: header.to_string(),
+        algorithm,
+        encoding,
+        prefix: str("prefix").unwrap_or_default().to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_webhooks() {
+        let mut value: Value = serde_yaml::from_str(
+            "openapi: 3.1.0
+info: {title: test, version: '1'}
+x-webhook-event: {field: type}
+x-webhook-signature: {header: X-Signature, encoding: base64}
+paths:
+  /subscribe:
+    post:
+      operationId: subscribe
+      callbacks:
+        onEvent:
+          '{$request.body#/url}':
+            post:
+              operationId: eventHappened
+              requestBody:
+                content:
+                  application/json:
+                    schema: {$ref: '#/components/schemas/Pet'}
+              responses: {'200': {description: ok}}
+      responses: {'200': {description: ok}}
+webhooks:
+  newPet:
+    post:
+      x-event-name: pet.created
+      requestBody:
+        content:
+          application/json:
+            schema: {$ref: '#/components/schemas/Pet'}
+      responses: {'200': {description: ok}}
+  ping:
+    post:
+      responses: {'200': {description: ok}}
+components:
+  schemas:
+    Pet: {type: object, properties: {name: {type: string}}}",
+        )
+        .unwrap();
+        preserve_webhooks(&mut value);
+        let spec: OpenAPI = serde_json::from_value(value).unwrap();
+        let mut hir = HirSpec::default();
+        extract_webhooks(&spec, &mut hir);
+        let webhooks = hir
+            .webhooks
+            .iter()
+            .map(|w| {
+                (
+                    w.name.as_str(),
+                    w.event.as_str(),
+                    w.body.inner_model().map(|m| m.as_str()),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            webhooks,
+            [
+                ("NewPet", "pet.created", Some("Pet")),
+                ("Ping", "ping", None),
+                ("EventHappened", "eventHappened", Some("Pet")),
+            ]
+        );
+        assert!(matches!(hir.webhooks[1].body, Ty::Unit));
+        assert_eq!(hir.webhook_event, Some(WebhookEvent::Field("type".to_string())));
+        let signature = hir.webhook_signature.unwrap();
+        assert_eq!(signature.algorithm, HmacAlgorithm::Sha256);
+        assert_eq!(signature.encoding, SignatureEncoding::Base64);
+    }
+}
@@ -0,0 +1,326 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+use convert_case::{Case, Casing};
+use openapiv3::OpenAPI;
+
+use codegen_rust::Report;
+use hir::{Config, OpenEnums};
+use mir::StringFormat;
+
+use crate::command::{parse_spec, read_spec};
+use crate::extractor::{
+    extract_spec, filter_operations, name_operations, remove_deprecated_operations, OperationFilter,
+};
+
+#[derive(Debug, Clone)]
+enum Source {
+    OpenApi(Box<OpenAPI>),
+    Path(PathBuf),
+    Text(String),
+}
+
+/// Generates a Rust client library from an OpenAPI spec, with the same options as `libninja gen`.
+///
+/// ```no_run
+/// use libninja::Generator;
+///
+/// let report = Generator::from_path("openapi.yaml")
+///     .name("Plaid")
+///     .derive("PartialEq")
+///     .output("plaid-rs")
+///     .generate()?;
+/// for path in &report.written {
+///     println!("{}", path.display());
+/// }
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+///
+/// The spec isn't read until [`Generator::generate`], which returns any error reading, parsing or generating it.
+#[derive(Debug, Clone)]
+pub struct Generator {
+    source: Source,
+    config: Config,
+    filter: OperationFilter,
+    strip_prefixes: Vec<String>,
+    strict_names: bool,
+}
+
+impl Generator {
+    /// Generate from a parsed spec. OpenAPI 3.1 `webhooks` and `callbacks` are dropped when parsing with `openapiv3`,
+    /// so use [`Generator::from_path`] or [`Generator::from_string`] for specs that have them.
+    pub fn new(spec: OpenAPI) -> Self {
+        Self::from_source(Source::OpenApi(Box::new(spec)))
+    }
+
+    /// Generate from a YAML or JSON file, by its extension.
+    pub fn from_path(path: impl Into<PathBuf>) -> Self {
+        Self::from_source(Source::Path(path.into()))
+    }
+
+    /// Generate from a YAML or JSON spec.
+    pub fn from_string(spec: impl Into<String>) -> Self {
+        Self::from_source(Source::Text(spec.into()))
+    }
+
+    fn from_source(source: Source) -> Self {
+        Self {
+            source,
+            config: Config {
+                dest: PathBuf::from("."),
+                build_examples: true,
+                ..Config::default()
+            },
+            filter: OperationFilter::default(),
+            strip_prefixes: Vec::new(),
+            strict_names: false,
+        }
+    }
+
+    /// The service name, e.g. `Stripe`, which names the client (`StripeClient`) and package. Required.
+    pub fn name(mut self, name: impl AsRef<str>) -> Self {
+        self.config.name = name.as_ref().to_case(Case::Pascal);
+        self
+    }
+
+    /// The directory of the library's crate. Defaults to the current directory.
+    pub fn output(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.config.dest = dir.into();
+        self
+    }
+
+    /// Replace the whole config, for options without a method here. The name isn't converted to Pascal case.
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Derive this trait on generated structs, e.g. `PartialEq` or `fake::Dummy`. Can be called repeatedly.
+    pub fn derive(mut self, derive: impl Into<String>) -> Self {
+        self.config.derives.push(derive.into());
+        self
+    }
+
+    /// Generate an example for every operation in `examples/`. Defaults to true.
+    pub fn examples(mut self, examples: bool) -> Self {
+        self.config.build_examples = examples;
+        self
+    }
+
+    /// Generate a rich type for this string format. Can be called repeatedly.
+    pub fn string_format(mut self, format: StringFormat) -> Self {
+        self.config.string_formats.push(format);
+        self
+    }
+
+    /// Which enums get an `Unknown(String)` variant. Defaults to enums in responses.
+    pub fn open_enums(mut self, open_enums: OpenEnums) -> Self {
+        self.config.open_enums = open_enums;
+        self
+    }
+
+    /// Generate every integer as i64 and every float as f64.
+    pub fn widen_numbers(mut self, widen: bool) -> Self {
+        self.config.widen_numbers = widen;
+        self
+    }
+
+    /// Don't call `validate()` on request params before sending requests.
+    pub fn skip_validation(mut self, skip: bool) -> Self {
+        self.config.skip_validation = skip;
+        self
+    }
+
+    /// Generate `{Name}Create` request models without read-only fields.
+    pub fn split_read_write(mut self, split: bool) -> Self {
+        self.config.split_read_write = split;
+        self
+    }
+
+    /// Leave out operations marked `deprecated`.
+    pub fn exclude_deprecated(mut self, exclude: bool) -> Self {
+        self.config.exclude_deprecated = exclude;
+        self
+    }
+
+    /// Also generate a mock server crate in `{output}/mock`.
+    pub fn mock_server(mut self, mock_server: bool) -> Self {
+        self.config.mock_server = mock_server;
+        self
+    }
+
+    /// Also generate `src/server.rs`, behind the `server` cargo feature.
+    pub fn server(mut self, server: bool) -> Self {
+        self.config.server = server;
+        self
+    }
+
+    /// Retry this operation even though its method isn't idempotent. Can be called repeatedly.
+    pub fn retry(mut self, operation: impl Into<String>) -> Self {
+        self.config.retry.push(operation.into());
+        self
+    }
+
+    /// Never retry this operation. Can be called repeatedly.
+    pub fn no_retry(mut self, operation: impl Into<String>) -> Self {
+        self.config.no_retry.push(operation.into());
+        self
+    }
+
+    /// Send a random idempotency key with POST and PATCH requests, in this header.
+    pub fn idempotency_key(mut self, header: impl Into<String>) -> Self {
+        self.config.idempotency_key = Some(header.into());
+        self
+    }
+
+    /// Group operations by their first tag into sub-clients.
+    pub fn tag_clients(mut self, tag_clients: bool) -> Self {
+        self.config.tag_clients = tag_clients;
+        self
+    }
+
+    /// Put each tag's operations, and the models only they use, behind a cargo feature.
+    pub fn tag_features(mut self, tag_features: bool) -> Self {
+        self.config.tag_features = tag_features;
+        self
+    }
+
+    /// Write `tests/serde_examples.rs`, which round-trips the spec's `example` values through the models.
+    pub fn example_tests(mut self, example_tests: bool) -> Self {
+        self.config.example_tests = example_tests;
+        self
+    }
+
+    /// Remove this prefix from operation names. Can be called repeatedly.
+    pub fn strip_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.strip_prefixes.push(prefix.into());
+        self
+    }
+
+    /// Error when operations have the same name, instead of renaming all but the first.
+    pub fn strict_names(mut self, strict: bool) -> Self {
+        self.strict_names = strict;
+        self
+    }
+
+    /// Only generate operations with this tag, or matching an included path. Can be called repeatedly.
+    pub fn include_tag(mut self, tag: impl Into<String>) -> Self {
+        self.filter.include_tags.push(tag.into());
+        self
+    }
+
+    /// Only generate operations whose path matches this glob, or with an included tag. Can be called repeatedly.
+    pub fn include_path(mut self, glob: impl Into<String>) -> Self {
+        self.filter.include_paths.push(glob.into());
+        self
+    }
+
+    /// Don't generate this operation, by `x-libninja-name` or `operationId`. Can be called repeatedly.
+    pub fn exclude_operation(mut self, operation: impl Into<String>) -> Self {
+        self.filter.exclude_operations.push(operation.into());
+        self
+    }
+
+    /// Replace the operation filter.
+    pub fn filter(mut self, filter: OperationFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Read the spec, and write the library to the output directory.
+    pub fn generate(self) -> Result<Report> {
+        if self.config.name.is_empty() {
+            bail!("The library needs a name. Set it with `Generator::name`.");
+        }
+        let mut spec = match self.source {
+            Source::OpenApi(spec) => *spec,
+            Source::Path(path) => read_spec(&path)?,
+            Source::Text(text) => parse_spec(&text)?,
+        };
+        filter_operations(&mut spec, &self.filter)?;
+        if self.config.exclude_deprecated {
+            remove_deprecated_operations(&mut spec);
+        }
+        name_operations(&mut spec, &self.strip_prefixes, self.strict_names)?;
+        let spec = extract_spec(&spec)?;
+        codegen_rust::generate_rust_library(spec, self.config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generator() {
+        let temp = tempfile::tempdir().unwrap();
+        let spec = "openapi: 3.0.0
+info: {title: test, version: '1'}
+paths:
+  /users:
+    get: {operationId: listUsers, tags: [user], responses: {'200': {description: ok}}}
+  /plans:
+    get: {operationId: listPlans, tags: [plan], responses: {'200': {description: ok}}}";
+        let generator = Generator::from_string(spec)
+            .name("pet store")
+            .include_tag("user")
+            .examples(false)
+            .output(temp.path());
+        let report = generator.clone().generate().unwrap();
+        assert!(report.written.contains(&temp.path().join("src/request/list_users.rs")));
+        assert!(!temp.path().join("src/request/list_plans.rs").exists());
+        let lib = std::fs::read_to_string(temp.path().join("src/lib.rs")).unwrap();
+        assert!(lib.contains("PetStoreClient"));
+        let manifest = std::fs::read_to_string(temp.path().join("Cargo.toml")).unwrap();
+        assert!(manifest.contains("http = \"1\""));
+        assert!(manifest.contains("tokio = { version = \"1\", features = [\"time\"] }"));
+
+        let report = generator.generate().unwrap();
+        assert!(report.written.is_empty());
+        assert!(report.unchanged.contains(&temp.path().join("src/lib.rs")));
+
+        let path = temp.path().join("spec.txt");
+        std::fs::write(&path, spec).unwrap();
+        let err = Generator::from_path(&path).name("Pet").generate().unwrap_err();
+        assert!(err
+            .to_string()
+            .ends_with("Unknown file extension txt. Expected yaml or json."));
+        let err = Generator::from_string(spec).generate().unwrap_err();
+        assert!(err.to_string().starts_with("The library needs a name"));
+    }
+
+    #[test]
+    fn test_example_tests() {
+        let temp = tempfile::tempdir().unwrap();
+        let spec = "openapi: 3.0.0
+info: {title: test, version: '1'}
+paths:
+  /user:
+    get:
+      operationId: getUser
+      responses:
+        '200':
+          description: ok
+          content: {application/json: {schema: {$ref: '#/components/schemas/User'}}}
+components:
+  schemas:
+    User:
+      type: object
+      properties: {name: {type: string, example: Ann}}";
+        let generator = Generator::from_string(spec).name("Pet").output(temp.path());
+        let tests = temp.path().join("tests/serde_examples.rs");
+        generator.clone().generate().unwrap();
+        assert!(!tests.exists());
+        let report = generator.clone().example_tests(true).generate().unwrap();
+        assert!(report.written.contains(&tests));
+
+        let mine = temp.path().join("tests/mine.rs");
+        std::fs::write(&mine, "#[test]\nfn mine() {}\n").unwrap();
+        let old_mock = temp.path().join("mock/src/old.rs");
+        std::fs::create_dir_all(old_mock.parent().unwrap()).unwrap();
+        std::fs::write(&old_mock, "").unwrap();
+        let report = generator.generate().unwrap();
+        assert_eq!(report.removed, [old_mock, tests]);
+        assert!(mine.exists());
+    }
+}
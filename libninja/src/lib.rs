@@ -1,5 +1,9 @@
+pub use codegen_rust::Report;
+pub use generator::Generator;
+
 pub mod command;
 pub mod extractor;
+mod generator;
 
 pub fn default<T: Default>() -> T {
     Default::default()
@@ -11,11 +11,13 @@ async fn main() {
             address: Some(AddressDataNotRequired {
                 city: Some("your city".to_owned()),
                 country: Some("your country".to_owned()),
+                extra: std::collections::HashMap::new(),
                 postal_code: Some("your postal code".to_owned()),
                 region: Some("your region".to_owned()),
                 street: Some("your street".to_owned()),
             }),
             email_address: Some("your email address".to_owned()),
+            extra: std::collections::HashMap::new(),
             legal_name: Some("your legal name".to_owned()),
             phone_number: Some("your phone number".to_owned()),
         })
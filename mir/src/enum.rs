@@ -14,5 +14,7 @@ pub struct Variant<T> {
     pub doc: Option<Doc>,
     // in rust, value is like enum { Error = 0 }
     pub value: Option<T>,
+    // in rust, fields are like enum { Other(String) }
+    pub fields: Vec<T>,
     pub attributes: Vec<T>,
 }
@@ -18,11 +18,107 @@ pub enum IntegerSerialization {
     NullAsZero,
 }
 
+/// Width and signedness of an integer, derived from the schema `format` and `minimum`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum IntegerSize {
+    I8,
+    I16,
+    I32,
+    #[default]
+    I64,
+    U8,
+    U16,
+    U32,
+    U64,
+}
+
+impl IntegerSize {
+    pub fn new(bits: u8, signed: bool) -> Self {
+        match (bits, signed) {
+            (8, true) => IntegerSize::I8,
+            (16, true) => IntegerSize::I16,
+            (32, true) => IntegerSize::I32,
+            (8, false) => IntegerSize::U8,
+            (16, false) => IntegerSize::U16,
+            (32, false) => IntegerSize::U32,
+            (_, false) => IntegerSize::U64,
+            (_, true) => IntegerSize::I64,
+        }
+    }
+
+    /// The smallest and largest values of the type.
+    pub fn range(self) -> (i128, i128) {
+        match self {
+            IntegerSize::I8 => (i8::MIN as i128, i8::MAX as i128),
+            IntegerSize::I16 => (i16::MIN as i128, i16::MAX as i128),
+            IntegerSize::I32 => (i32::MIN as i128, i32::MAX as i128),
+            IntegerSize::I64 => (i64::MIN as i128, i64::MAX as i128),
+            IntegerSize::U8 => (0, u8::MAX as i128),
+            IntegerSize::U16 => (0, u16::MAX as i128),
+            IntegerSize::U32 => (0, u32::MAX as i128),
+            IntegerSize::U64 => (0, u64::MAX as i128),
+        }
+    }
+}
+
+/// Precision of a floating point number, derived from the schema `format`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum FloatSize {
+    F32,
+    #[default]
+    F64,
+}
+
+/// A string schema whose `format` maps to a richer type than a plain string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StringFormat {
+    Uuid,
+    Uri,
+    Email,
+    Ip,
+    /// base64 encoded bytes
+    Byte,
+    /// raw bytes, e.g. a file upload
+    Binary,
+    /// ISO 8601 duration
+    Duration,
+}
+
+impl StringFormat {
+    pub fn from_format(format: &str) -> Option<Self> {
+        match format {
+            "uuid" => Some(StringFormat::Uuid),
+            "uri" | "url" => Some(StringFormat::Uri),
+            "email" => Some(StringFormat::Email),
+            "ip" | "ipv4" | "ipv6" => Some(StringFormat::Ip),
+            "byte" => Some(StringFormat::Byte),
+            "binary" => Some(StringFormat::Binary),
+            "duration" => Some(StringFormat::Duration),
+            _ => None,
+        }
+    }
+
+    /// Whether the value is decoded from its string form, rather than being a string-like type.
+    /// These need custom (de)serialization, so they are only kept on model fields.
+    pub fn is_decoded(&self) -> bool {
+        matches!(self, StringFormat::Byte | StringFormat::Binary | StringFormat::Duration)
+    }
+}
+
+impl std::str::FromStr for StringFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_format(s).ok_or_else(|| format!("Unknown string format: {}", s))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum Ty {
     String,
-    Integer { ser: IntegerSerialization },
-    Float,
+    FormattedString(StringFormat),
+    Integer { ser: IntegerSerialization, size: IntegerSize },
+    Float { size: FloatSize },
     Boolean,
     Array(Box<Ty>),
     HashMap(Box<Ty>),
@@ -45,6 +141,23 @@ impl Ty {
     pub fn integer() -> Self {
         Ty::Integer {
             ser: IntegerSerialization::Simple,
+            size: IntegerSize::default(),
+        }
+    }
+
+    pub fn float() -> Self {
+        Ty::Float {
+            size: FloatSize::default(),
+        }
+    }
+
+    /// Discard width and precision, so every integer is 64-bit signed and every float is 64-bit.
+    pub fn widen_numbers(&mut self) {
+        match self {
+            Ty::Integer { size, .. } => *size = IntegerSize::I64,
+            Ty::Float { size } => *size = FloatSize::F64,
+            Ty::Array(inner) | Ty::HashMap(inner) => inner.widen_numbers(),
+            _ => {}
         }
     }
 
@@ -71,8 +184,9 @@ impl Ty {
     pub fn is_primitive(&self) -> bool {
         match self {
             Ty::String => true,
+            Ty::FormattedString(_) => true,
             Ty::Integer { .. } => true,
-            Ty::Float => true,
+            Ty::Float { .. } => true,
             Ty::Boolean => true,
             Ty::Array(_) => false,
             Ty::HashMap(_) => false,
@@ -85,6 +199,16 @@ impl Ty {
         }
     }
 
+    /// Turn formatted strings back into plain strings, unless `keep` accepts the format. Decoded formats
+    /// need a serde helper on the field, so they are never kept inside arrays or maps.
+    pub fn erase_string_formats(&mut self, keep: &dyn Fn(StringFormat) -> bool) {
+        match self {
+            Ty::FormattedString(format) if !keep(*format) => *self = Ty::String,
+            Ty::Array(inner) | Ty::HashMap(inner) => inner.erase_string_formats(&|f| keep(f) && !f.is_decoded()),
+            _ => {}
+        }
+    }
+
     pub fn model(s: &str) -> Self {
         if s.contains('(') {
             panic!("Model names should not contain parens: {}", s);
@@ -1,7 +1,11 @@
-use crate::{derives_to_tokens, CanDerive, ToRustCode, ToRustIdent, ToRustType};
+use crate::default::to_rust_default_value;
+use crate::{
+    deprecated_to_tokens, derives_to_tokens, make_validate_method, CanDerive, ToRustCode, ToRustIdent, ToRustType,
+};
 use hir::{Config, HirField, HirSpec, Struct};
 use mir::{
-    Class, DateSerialization, DecimalSerialization, Field, Function, Ident, IntegerSerialization, Item, Ty, Visibility,
+    Class, DateSerialization, DecimalSerialization, Field, Function, Ident, IntegerSerialization, Item, StringFormat, Ty,
+    Visibility,
 };
 use proc_macro2::{Span, TokenStream};
 use quote::quote;
@@ -82,15 +86,21 @@ impl ToRustCode for Field<TokenStream> {
     }
 }
 
-fn field_attributes(f: &HirField, name: &str, config: &Config) -> Vec<TokenStream> {
-    let mut attributes = Vec::new();
+/// `default_fn` is the path of a function returning the field's spec `default`, if it has one.
+fn field_attributes(f: &HirField, name: &str, config: &Config, default_fn: Option<&str>) -> Vec<TokenStream> {
+    let mut attributes = vec![deprecated_to_tokens(f.deprecated)];
+    let default = match default_fn {
+        Some(path) => quote!(default = #path),
+        None => quote!(default),
+    };
     let rust_ident = name.to_rust_ident();
+    if f.flatten {
+        attributes.push(quote! {
+            #[serde(flatten)]
+        });
+    }
     if rust_ident != name {
-        if f.flatten {
-            attributes.push(quote! {
-                #[serde(flatten)]
-            });
-        } else {
+        if !f.flatten {
             attributes.push(quote! {
                 #[serde(rename = #name)]
             });
@@ -101,17 +111,31 @@ fn field_attributes(f: &HirField, name: &str, config: &Config) -> Vec<TokenStrea
             });
         }
     }
-    if f.optional {
+    if f.read_only {
+        if f.optional || f.ty.is_iterable() || matches!(f.ty, Ty::Any(_)) || default_fn.is_some() {
+            attributes.push(quote! {
+                #[serde(#default, skip_serializing)]
+            });
+        } else {
+            attributes.push(quote! {
+                #[serde(skip_serializing)]
+            });
+        }
+    } else if f.optional || f.write_only {
         attributes.push(quote! {
-            #[serde(default, skip_serializing_if = "Option::is_none")]
+            #[serde(#default, skip_serializing_if = "Option::is_none")]
         });
     } else if f.ty.is_iterable() {
         attributes.push(quote! {
-            #[serde(default, skip_serializing_if = "Vec::is_empty")]
+            #[serde(#default, skip_serializing_if = "Vec::is_empty")]
         });
     } else if matches!(f.ty, Ty::Any(_)) {
         attributes.push(quote! {
-            #[serde(default, skip_serializing_if = "serde_json::Value::is_null")]
+            #[serde(#default, skip_serializing_if = "serde_json::Value::is_null")]
+        });
+    } else if default_fn.is_some() {
+        attributes.push(quote! {
+            #[serde(#default)]
         });
     }
     if f.ty.inner_model().is_some() && config.ormlite {
@@ -120,7 +144,7 @@ fn field_attributes(f: &HirField, name: &str, config: &Config) -> Vec<TokenStrea
         });
     }
     match f.ty {
-        Ty::Integer { ser: serialization } => match serialization {
+        Ty::Integer { ser: serialization, .. } => match serialization {
             IntegerSerialization::Simple => {}
             IntegerSerialization::String => {
                 attributes.push(quote! {
@@ -141,10 +165,25 @@ fn field_attributes(f: &HirField, name: &str, config: &Config) -> Vec<TokenStrea
                 });
             }
         },
+        Ty::FormattedString(StringFormat::Byte) => {
+            attributes.push(quote! {
+                #[serde(with = "crate::serde::option_base64_bytes")]
+            });
+        }
+        Ty::FormattedString(StringFormat::Binary) => {
+            attributes.push(quote! {
+                #[serde(with = "crate::serde::option_binary_string")]
+            });
+        }
+        Ty::FormattedString(StringFormat::Duration) => {
+            attributes.push(quote! {
+                #[serde(with = "crate::serde::option_iso8601_duration")]
+            });
+        }
         Ty::Currency {
             ser: DecimalSerialization::String,
         } => {
-            if f.optional {
+            if f.optional || f.write_only {
                 attributes.push(quote! {
                     #[serde(with = "rust_decimal::serde::str_option")]
                 });
@@ -159,32 +198,53 @@ fn field_attributes(f: &HirField, name: &str, config: &Config) -> Vec<TokenStrea
     attributes
 }
 
-fn class_fields(s: &Struct, config: &Config) -> Vec<Field<TokenStream>> {
+/// Whether the generated field is an `Option`. Fields using a custom serde helper always are.
+fn is_optional(field: &HirField) -> bool {
+    match field.ty {
+        Ty::Integer {
+            ser: IntegerSerialization::NullAsZero | IntegerSerialization::String,
+            ..
+        } => true,
+        Ty::Date {
+            ser: DateSerialization::Integer,
+        } => true,
+        Ty::FormattedString(StringFormat::Byte | StringFormat::Binary | StringFormat::Duration) => true,
+        // Write-only fields are missing from responses.
+        _ => field.optional || field.write_only,
+    }
+}
+
+/// The field's spec `default` as an expression, if it can be written for its type.
+fn field_default(field: &HirField, spec: &HirSpec) -> Option<TokenStream> {
+    let value = to_rust_default_value(&field.ty, field.default.as_ref()?, spec)?;
+    if is_optional(field) {
+        Some(quote!(Some(#value)))
+    } else {
+        Some(value)
+    }
+}
+
+fn default_fn_name(name: &str) -> Ident {
+    format!("default_{}", name.to_rust_ident().0).to_rust_ident()
+}
+
+fn class_fields(s: &Struct, config: &Config, spec: &HirSpec) -> Vec<Field<TokenStream>> {
     s.fields
         .iter()
         .map(|(name, field)| {
-            let attributes = field_attributes(field, name, config);
-            let ty = field.ty.to_rust_type();
-            let mut optional = field.optional;
-            match field.ty {
-                Ty::Integer {
-                    ser: IntegerSerialization::NullAsZero | IntegerSerialization::String,
-                } => {
-                    optional = true;
-                }
-                Ty::Date {
-                    ser: DateSerialization::Integer,
-                } => {
-                    optional = true;
-                }
-                _ => {}
+            let default_fn = field_default(field, spec)
+                .map(|_| format!("{}::{}", s.name.to_rust_struct().0, default_fn_name(name).0));
+            let attributes = field_attributes(field, name, config, default_fn.as_deref());
+            let mut ty = field.ty.to_rust_type();
+            if field.boxed {
+                ty = quote!(Box<#ty>);
             }
             Field {
                 name: name.to_rust_ident(),
                 ty,
                 vis: Visibility::Public,
                 attributes,
-                optional,
+                optional: is_optional(field),
                 doc: field.doc.clone(),
                 ..Field::default()
             }
@@ -195,20 +255,27 @@ fn class_fields(s: &Struct, config: &Config) -> Vec<Field<TokenStream>> {
 pub struct RefTarget {
     name: String,
     ty: Ty,
+    boxed: bool,
 }
 
 fn ref_target(s: &Struct) -> Option<RefTarget> {
     s.fields
         .iter()
-        .find(|(_, f)| f.flatten && !f.optional)
+        .find(|(_, f)| f.flatten && !f.optional && matches!(f.ty, Ty::Model(_)))
         .map(|(name, f)| RefTarget {
             name: name.clone(),
             ty: f.ty.clone(),
+            boxed: f.boxed,
         })
 }
 
 pub fn make_class(s: &Struct, config: &Config, spec: &HirSpec) -> Class<TokenStream> {
-    let default = s.implements_default(spec).then(|| {
+    let defaults = s
+        .fields
+        .iter()
+        .filter_map(|(name, f)| Some((name, f, field_default(f, spec)?)))
+        .collect::<Vec<_>>();
+    let derive_default = (s.implements_default(spec) && defaults.is_empty()).then(|| {
         quote! { , Default }
     });
     let derives = derives_to_tokens(&config.derives);
@@ -216,11 +283,14 @@ pub fn make_class(s: &Struct, config: &Config, spec: &HirSpec) -> Class<TokenStr
     let doc = s.docs.clone();
 
     let name = s.name.to_rust_struct();
-    let fields = class_fields(s, config);
+    let fields = class_fields(s, config, spec);
     let deref = ref_target(s)
         .map(|t| {
             let target = t.name.to_rust_ident();
-            let ty = t.ty.to_rust_type();
+            let mut ty = t.ty.to_rust_type();
+            if t.boxed {
+                ty = quote!(Box<#ty>);
+            }
             quote! {
                 impl std::ops::Deref for #name {
                     type Target = #ty;
@@ -237,23 +307,76 @@ pub fn make_class(s: &Struct, config: &Config, spec: &HirSpec) -> Class<TokenStr
         })
         .unwrap_or_default();
 
+    let impl_default = if s.implements_default(spec) && !defaults.is_empty() {
+        let values = s.fields.keys().map(|field| {
+            let ident = field.to_rust_ident();
+            if defaults.iter().any(|(n, _, _)| *n == field) {
+                let default_fn = default_fn_name(field);
+                quote! { #ident: Self::#default_fn() }
+            } else {
+                quote! { #ident: Default::default() }
+            }
+        });
+        quote! {
+            impl Default for #name {
+                fn default() -> Self {
+                    Self {
+                        #(#values,)*
+                    }
+                }
+            }
+        }
+    } else {
+        TokenStream::new()
+    };
+
     let impl_blocks = quote! {
         impl std::fmt::Display for #name {
             fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
                 write!(f, "{}", serde_json::to_string(self).unwrap())
             }
         }
+        #impl_default
         #deref
     };
-    let attributes = vec![quote! {
-        #[derive(Debug, Clone, Serialize, Deserialize #default #derives)]
-    }];
+    let attributes = vec![
+        quote! {
+            #[derive(Debug, Clone, Serialize, Deserialize #derive_default #derives)]
+        },
+        deprecated_to_tokens(s.deprecated),
+    ];
+    let mut methods = defaults
+        .into_iter()
+        .map(|(field, f, value)| {
+            let mut ret = f.ty.to_rust_type();
+            if is_optional(f) {
+                ret = quote!(Option<#ret>);
+            }
+            Function {
+                name: default_fn_name(field),
+                ret,
+                body: value,
+                vis: Visibility::Private,
+                ..Function::default()
+            }
+        })
+        .collect::<Vec<_>>();
+    let validate_fields = s
+        .fields
+        .iter()
+        .map(|(name, field)| {
+            let mut field = field.clone();
+            field.optional = is_optional(&field);
+            (name.clone(), field)
+        })
+        .collect::<Vec<_>>();
+    methods.extend(make_validate_method(&validate_fields, spec));
     Class {
         vis: Visibility::Public,
         name,
         doc,
         fields,
-        methods: vec![],
+        methods,
         attributes,
         lifetimes: vec![],
         items: vec![Item::Block(impl_blocks)],
@@ -262,11 +385,14 @@ pub fn make_class(s: &Struct, config: &Config, spec: &HirSpec) -> Class<TokenStr
 }
 
 impl CanDerive for Struct {
-    fn implements_default(&self, spec: &HirSpec) -> bool {
-        self.fields.iter().all(|(_, f)| f.ty.implements_default(spec))
+    /// Optional fields and fields with a usable spec `default` don't need their type to implement Default.
+    fn implements_default_inner(&self, spec: &HirSpec, path: &mut Vec<String>) -> bool {
+        self.fields.iter().all(|(_, f)| {
+            is_optional(f) || f.ty.implements_default_inner(spec, path) || field_default(f, spec).is_some()
+        })
     }
 
-    fn implements_dummy(&self, spec: &HirSpec) -> bool {
-        self.fields.iter().all(|(_, f)| f.ty.implements_dummy(spec))
+    fn implements_dummy_inner(&self, spec: &HirSpec, path: &mut Vec<String>) -> bool {
+        self.fields.iter().all(|(_, f)| f.ty.implements_dummy_inner(spec, path))
     }
 }
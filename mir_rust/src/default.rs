@@ -0,0 +1,59 @@
+use hir::{HirSpec, Record};
+use mir::Ty;
+use proc_macro2::TokenStream;
+use quote::quote;
+use serde_json::Value;
+
+use crate::ToRustIdent;
+
+/// Convert a schema `default` to an expression of type `ty`, or `None` if the value doesn't fit the
+/// type or we can't write it as a constant (e.g. dates, nested models).
+pub fn to_rust_default_value(ty: &Ty, value: &Value, spec: &HirSpec) -> Option<TokenStream> {
+    let tokens = match (ty, value) {
+        (Ty::String, Value::String(s)) => quote!(#s.to_string()),
+        (Ty::Integer { .. }, Value::Number(n)) => n.as_i64()?.to_string().parse().ok()?,
+        (Ty::Float { .. }, Value::Number(n)) => format!("{:?}", n.as_f64()?).parse().ok()?,
+        (Ty::Boolean, Value::Bool(b)) => quote!(#b),
+        (Ty::Array(inner), Value::Array(items)) => {
+            let items = items
+                .iter()
+                .map(|item| to_rust_default_value(inner, item, spec))
+                .collect::<Option<Vec<_>>>()?;
+            quote!(vec![#(#items),*])
+        }
+        (Ty::Model(name), value) => {
+            let Ok(Record::Enum(e)) = spec.get_record(name) else {
+                return None;
+            };
+            let value = match value {
+                Value::String(s) => s.clone(),
+                Value::Number(n) => n.to_string(),
+                _ => return None,
+            };
+            let (variant, _) = e.iter_safe_variant_names().find(|(_, v)| *v == value)?;
+            let model = name.to_rust_struct();
+            let variant = variant.to_rust_struct();
+            quote!(#model::#variant)
+        }
+        _ => return None,
+    };
+    Some(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_default_value() {
+        let spec = HirSpec::default();
+        let value = |ty: &Ty, v: Value| to_rust_default_value(ty, &v, &spec).map(|t| t.to_string());
+        assert_eq!(value(&Ty::integer(), json!(20)).unwrap(), "20");
+        assert_eq!(value(&Ty::float(), json!(1)).unwrap(), "1.0");
+        assert_eq!(value(&Ty::String, json!("a")).unwrap(), "\"a\" . to_string ()");
+        assert_eq!(value(&Ty::Array(Box::new(Ty::Boolean)), json!([true])).unwrap(), "vec ! [true]");
+        assert_eq!(value(&Ty::String, json!(1)), None);
+        assert_eq!(value(&Ty::DateTime, json!("2020-01-01T00:00:00Z")), None);
+    }
+}
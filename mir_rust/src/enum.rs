@@ -1,11 +1,14 @@
 use crate::ident::ToRustIdent;
-use crate::{derives_to_tokens, serde_rename2, ToRustCode};
-use mir::{Enum, Item, Variant, Visibility};
+use crate::{deprecated_to_tokens, derives_to_tokens, serde_rename2, ToRustCode};
+use mir::{Doc, Enum, Item, Variant, Visibility};
 use proc_macro2::TokenStream;
 use quote::quote;
 
 pub fn make_enum(e: &hir::Enum, derives: &[String]) -> Item<TokenStream> {
-    let variants = e
+    if e.is_integer() {
+        return make_integer_enum(e, derives);
+    }
+    let mut variants = e
         .iter_safe_variant_names()
         .map(|(name, value)| {
             let ident = name.to_rust_struct();
@@ -14,10 +17,16 @@ pub fn make_enum(e: &hir::Enum, derives: &[String]) -> Item<TokenStream> {
                 ident,
                 doc: None,
                 value: None,
+                fields: Vec::new(),
                 attributes: rename.into_iter().collect(),
             }
         })
-        .collect();
+        .collect::<Vec<_>>();
+    if e.open.unwrap_or_default() {
+        let mut unknown = unknown_variant(quote!(String));
+        unknown.attributes.push(quote!(#[serde(untagged)]));
+        variants.push(unknown);
+    }
     let derives = derives_to_tokens(derives);
     let derives = quote! { #[derive(Debug, Serialize, Deserialize, Clone #derives)] };
     Item::Enum(Enum {
@@ -26,7 +35,102 @@ pub fn make_enum(e: &hir::Enum, derives: &[String]) -> Item<TokenStream> {
         variants,
         vis: Visibility::Public,
         methods: Vec::new(),
-        attributes: vec![derives],
+        attributes: vec![derives, deprecated_to_tokens(e.deprecated)],
+    })
+}
+
+fn unknown_variant(ty: TokenStream) -> Variant<TokenStream> {
+    Variant {
+        ident: "Unknown".to_rust_struct(),
+        doc: Some(Doc("A value that wasn't in the API spec when this library was generated.".to_string())),
+        value: None,
+        fields: vec![ty],
+        attributes: vec![],
+    }
+}
+
+/// Integer enums (de)serialize as their number, like serde_repr, but implemented inline so the
+/// open variant can keep unknown numbers.
+fn make_integer_enum(e: &hir::Enum, derives: &[String]) -> Item<TokenStream> {
+    let name = e.name.to_rust_struct();
+    let open = e.open.unwrap_or_default();
+    let variants = e
+        .iter_safe_variant_names()
+        .zip(&e.variants)
+        .map(|((ident, _), v)| (ident.to_rust_struct(), v.discriminant.unwrap()))
+        .collect::<Vec<_>>();
+    let number = |n: i64| n.to_string().parse::<TokenStream>().unwrap();
+
+    let mut enum_variants = variants
+        .iter()
+        .map(|(ident, n)| Variant {
+            ident: ident.clone(),
+            doc: None,
+            value: (!open).then(|| number(*n)),
+            fields: Vec::new(),
+            attributes: Vec::new(),
+        })
+        .collect::<Vec<_>>();
+    let mut attributes = vec![{
+        let derives = derives_to_tokens(derives);
+        quote! { #[derive(Debug, Clone #derives)] }
+    }];
+    attributes.push(deprecated_to_tokens(e.deprecated));
+    let (serialize_unknown, deserialize_unknown) = if open {
+        enum_variants.push(unknown_variant(quote!(i64)));
+        (
+            quote! { #name::Unknown(value) => *value, },
+            quote! { value => Ok(#name::Unknown(value)), },
+        )
+    } else {
+        attributes.push(quote! { #[repr(i64)] });
+        let expected = format!(
+            "one of {}",
+            variants.iter().map(|(_, n)| n.to_string()).collect::<Vec<_>>().join(", ")
+        );
+        (
+            TokenStream::new(),
+            quote! {
+                value => Err(serde::de::Error::invalid_value(serde::de::Unexpected::Signed(value), &#expected)),
+            },
+        )
+    };
+    let to_number = variants.iter().map(|(ident, n)| {
+        let n = number(*n);
+        quote! { #name::#ident => #n, }
+    });
+    let from_number = variants.iter().map(|(ident, n)| {
+        let n = number(*n);
+        quote! { #n => Ok(#name::#ident), }
+    });
+    let enum_code = Enum {
+        name: name.clone(),
+        doc: e.doc.clone(),
+        variants: enum_variants,
+        vis: Visibility::Public,
+        methods: Vec::new(),
+        attributes,
+    }
+    .to_rust_code();
+    Item::Block(quote! {
+        #enum_code
+        impl Serialize for #name {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                let value = match self {
+                    #(#to_number)*
+                    #serialize_unknown
+                };
+                serializer.serialize_i64(value)
+            }
+        }
+        impl<'de> Deserialize<'de> for #name {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                match i64::deserialize(deserializer)? {
+                    #(#from_number)*
+                    #deserialize_unknown
+                }
+            }
+        }
     })
 }
 
@@ -70,14 +174,20 @@ impl ToRustCode for Variant<TokenStream> {
             ident,
             doc,
             value,
+            fields,
             attributes,
         } = self;
         let doc = doc.to_rust_code();
         let value = value.map(|v| quote!(= #v)).unwrap_or_default();
+        let fields = if fields.is_empty() {
+            TokenStream::new()
+        } else {
+            quote!((#(#fields),*))
+        };
         quote! {
             #doc
             #(#attributes)*
-            #ident #value
+            #ident #fields #value
         }
     }
 }
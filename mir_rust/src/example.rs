@@ -2,11 +2,16 @@ use crate::ident::ToRustIdent;
 use crate::ty::ToRustType;
 use convert_case::{Case, Casing};
 use hir::{Enum, HirField, HirSpec, NewType, Record, Struct};
-use mir::Ty;
+use mir::{StringFormat, Ty};
 use proc_macro2::TokenStream;
 use quote::quote;
 
 pub fn to_rust_example_value(ty: &Ty, name: &str, spec: &HirSpec, use_ref_value: bool) -> TokenStream {
+    example_value(ty, name, spec, use_ref_value, &mut Vec::new())
+}
+
+/// `path` holds the models being built, so recursive models stop at the first repeat.
+fn example_value(ty: &Ty, name: &str, spec: &HirSpec, use_ref_value: bool, path: &mut Vec<String>) -> TokenStream {
     match ty {
         Ty::String => {
             let s = format!("your {}", name.to_case(Case::Lower));
@@ -16,8 +21,16 @@ pub fn to_rust_example_value(ty: &Ty, name: &str, spec: &HirSpec, use_ref_value:
                 quote!(#s.to_owned())
             }
         }
+        Ty::FormattedString(format) => match format {
+            StringFormat::Uuid => quote!(uuid::Uuid::nil()),
+            StringFormat::Uri => quote!(url::Url::parse("https://example.com").unwrap()),
+            StringFormat::Email => quote!("user@example.com".parse::<email_address::EmailAddress>().unwrap()),
+            StringFormat::Ip => quote!(std::net::IpAddr::from([127, 0, 0, 1])),
+            StringFormat::Byte | StringFormat::Binary => quote!(vec![]),
+            StringFormat::Duration => quote!(std::time::Duration::from_secs(1)),
+        },
         Ty::Integer { .. } => quote!(1),
-        Ty::Float => quote!(1.0),
+        Ty::Float { .. } => quote!(1.0),
         Ty::Boolean => quote!(true),
         Ty::Array(inner) => {
             let use_ref_value = if !inner.is_reference_type() {
@@ -25,32 +38,46 @@ pub fn to_rust_example_value(ty: &Ty, name: &str, spec: &HirSpec, use_ref_value:
             } else {
                 use_ref_value
             };
-            let inner = to_rust_example_value(inner, name, spec, use_ref_value);
+            if inner.inner_model().is_some_and(|m| path.contains(m)) {
+                return quote!(vec![]);
+            }
+            let inner = example_value(inner, name, spec, use_ref_value, path);
             if use_ref_value {
                 quote!(&[#inner])
             } else {
                 quote!(vec![#inner])
             }
         }
+        Ty::Model(model) if path.contains(model) => quote!(Default::default()),
         Ty::Model(model) => {
             let record = spec.get_record(model).expect("record not found");
             let force_ref = model.ends_with("Required");
-            match record {
+            path.push(model.clone());
+            let value = match record {
                 Record::Struct(Struct {
                     name: _name,
                     fields,
                     nullable: _,
                     docs: _docs,
+                    deprecated: _,
+                    example: _,
                 }) => {
                     let fields = fields.iter().map(|(name, field)| {
                         let not_ref = !force_ref || field.optional;
-                        let mut value = to_rust_example_value(&field.ty, name, spec, !not_ref);
-                        let name = name.to_rust_ident();
+                        let ident = name.to_rust_ident();
+                        if field.optional && field.ty.inner_model().is_some_and(|m| path.contains(m)) {
+                            return quote!(#ident: None);
+                        }
+                        let mut value = example_value(&field.ty, name, spec, !not_ref, path);
+                        if field.boxed {
+                            value = quote!(Box::new(#value));
+                        }
                         if field.optional {
                             value = quote!(Some(#value));
                         }
-                        quote!(#name: #value)
+                        quote!(#ident: #value)
                     });
+                    let fields = fields.collect::<Vec<_>>();
                     let model = model.to_rust_struct();
                     quote!(#model{#(#fields),*}).into()
                 }
@@ -58,8 +85,19 @@ pub fn to_rust_example_value(ty: &Ty, name: &str, spec: &HirSpec, use_ref_value:
                     name,
                     fields,
                     doc: _docs,
+                    deprecated: _,
                 }) => {
-                    let fields = fields.iter().map(|f| to_rust_example_value(&f.ty, name, spec, false));
+                    let fields = fields
+                        .iter()
+                        .map(|f| {
+                            let value = example_value(&f.ty, name, spec, false, path);
+                            if f.boxed {
+                                quote!(Box::new(#value))
+                            } else {
+                                value
+                            }
+                        })
+                        .collect::<Vec<_>>();
                     let name = name.to_rust_struct();
                     quote!(#name(#(#fields),*))
                 }
@@ -69,16 +107,21 @@ pub fn to_rust_example_value(ty: &Ty, name: &str, spec: &HirSpec, use_ref_value:
                     let model = model.to_rust_struct();
                     quote!(#model::#variant)
                 }
-                Record::TypeAlias(name, HirField { ty, optional, .. }) => {
+                Record::TypeAlias(name, HirField { ty, optional, boxed, .. }) => {
                     let not_ref = !force_ref || !optional;
-                    let ty = to_rust_example_value(ty, name, spec, not_ref);
+                    let mut ty = example_value(ty, name, spec, not_ref, path);
+                    if *boxed {
+                        ty = quote!(Box::new(#ty));
+                    }
                     if *optional {
                         quote!(Some(#ty))
                     } else {
                         quote!(#ty)
                     }
                 }
-            }
+            };
+            path.pop();
+            value
         }
         Ty::Unit => quote!(()),
         Ty::Any(_) => quote!(serde_json::json!({})),
@@ -8,6 +8,7 @@ use quote::quote;
 use regex::{Captures, Regex};
 
 pub mod class;
+mod default;
 mod r#enum;
 mod example;
 mod file;
@@ -17,6 +18,7 @@ mod import;
 mod record;
 mod ty;
 mod util;
+mod validate;
 
 pub use example::to_rust_example_value;
 
@@ -25,6 +27,7 @@ pub use r#enum::make_enum;
 pub use record::make_item;
 pub use ty::{CanDerive, ToRustType};
 pub use util::*;
+pub use validate::make_validate_method;
 
 pub fn serde_rename2(value: &str, ident: &Ident) -> Option<TokenStream> {
     if ident.0 != value {
@@ -47,6 +50,15 @@ pub fn derives_to_tokens(derives: &[String]) -> TokenStream {
         .collect()
 }
 
+/// `#[deprecated]` for anything marked `deprecated` in the API spec.
+pub fn deprecated_to_tokens(deprecated: bool) -> TokenStream {
+    if deprecated {
+        quote! { #[deprecated(note = "Deprecated in the API spec.")] }
+    } else {
+        TokenStream::new()
+    }
+}
+
 /// Use this for codegen structs: Function, Class, etc.
 pub trait ToRustCode {
     fn to_rust_code(self) -> TokenStream;
@@ -132,6 +144,8 @@ fn rewrite_names(s: &str) -> String {
         .replace(['@', '\'', '+'], "")
         .replace(':', " ")
         .replace('.', "_")
+        // Any other punctuation, e.g. `&` in a tag, separates words.
+        .replace(|c: char| !c.is_alphanumeric() && !matches!(c, '_' | '-' | ' ' | '(' | ')'), " ")
 }
 
 pub fn sanitize(s: impl AsRef<str>) -> String {
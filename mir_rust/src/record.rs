@@ -1,5 +1,5 @@
 use crate::ty::CanDerive;
-use crate::{derives_to_tokens, make_class, make_enum, ToRustIdent, ToRustType};
+use crate::{deprecated_to_tokens, derives_to_tokens, make_class, make_enum, ToRustIdent, ToRustType};
 use hir::{Config, HirField, HirSpec, NewType, Record};
 use mir::Item;
 use proc_macro2::TokenStream;
@@ -7,7 +7,14 @@ use quote::quote;
 
 pub fn make_newtype(schema: &NewType, spec: &HirSpec, derives: &Vec<String>) -> Item<TokenStream> {
     let name = schema.name.to_rust_struct();
-    let fields = schema.fields.iter().map(|f| f.ty.to_rust_type());
+    let fields = schema.fields.iter().map(|f| {
+        let ty = f.ty.to_rust_type();
+        if f.boxed {
+            quote!(Box<#ty>)
+        } else {
+            ty
+        }
+    });
     let derives = derives_to_tokens(derives);
     let default = schema
         .fields
@@ -17,8 +24,10 @@ pub fn make_newtype(schema: &NewType, spec: &HirSpec, derives: &Vec<String>) ->
             quote! { , Default }
         })
         .unwrap_or_default();
+    let deprecated = deprecated_to_tokens(schema.deprecated);
     Item::Block(quote! {
         #[derive(Debug, Clone, Serialize, Deserialize #default #derives)]
+        #deprecated
         pub struct #name(#(pub #fields),*);
     })
 }
@@ -26,10 +35,15 @@ pub fn make_newtype(schema: &NewType, spec: &HirSpec, derives: &Vec<String>) ->
 pub fn make_typealias(name: &str, schema: &HirField) -> Item<TokenStream> {
     let name = name.to_rust_struct();
     let mut ty = schema.ty.to_rust_type();
+    if schema.boxed {
+        ty = quote! { Box<#ty> };
+    }
     if schema.optional {
         ty = quote! { Option<#ty> };
     }
+    let deprecated = deprecated_to_tokens(schema.deprecated);
     Item::Block(quote! {
+        #deprecated
         pub type #name = #ty;
     })
 }
@@ -44,17 +58,17 @@ pub fn make_item(record: &Record, spec: &HirSpec, cfg: &Config) -> Item<TokenStr
 }
 
 impl CanDerive for Record {
-    fn implements_default(&self, spec: &HirSpec) -> bool {
+    fn implements_default_inner(&self, spec: &HirSpec, path: &mut Vec<String>) -> bool {
         match self {
             Record::Enum(_) => false,
-            _ => self.fields().all(|f| f.ty.implements_default(spec)),
+            _ => self.fields().all(|f| f.ty.implements_default_inner(spec, path)),
         }
     }
 
-    fn implements_dummy(&self, spec: &HirSpec) -> bool {
+    fn implements_dummy_inner(&self, spec: &HirSpec, path: &mut Vec<String>) -> bool {
         match self {
             Record::Enum(_) => false,
-            _ => self.fields().all(|f| f.ty.implements_default(spec)),
+            _ => self.fields().all(|f| f.ty.implements_default_inner(spec, path)),
         }
     }
 }
@@ -75,6 +89,7 @@ mod tests {
                 ..HirField::default()
             }],
             doc: None,
+            deprecated: false,
         };
         let code = make_newtype(&schema, &HirSpec::default(), &vec![]);
         let code = format_code(code.to_rust_code());
@@ -1,6 +1,6 @@
 use crate::ident::ToRustIdent;
 use hir::HirSpec;
-use mir::Ty;
+use mir::{FloatSize, IntegerSize, StringFormat, Ty};
 use proc_macro2::TokenStream;
 use quote::quote;
 
@@ -12,16 +12,38 @@ pub trait ToRustType {
 }
 
 pub trait CanDerive {
-    fn implements_default(&self, spec: &HirSpec) -> bool;
-    fn implements_dummy(&self, spec: &HirSpec) -> bool;
+    fn implements_default(&self, spec: &HirSpec) -> bool {
+        self.implements_default_inner(spec, &mut Vec::new())
+    }
+
+    fn implements_dummy(&self, spec: &HirSpec) -> bool {
+        self.implements_dummy_inner(spec, &mut Vec::new())
+    }
+
+    /// `path` holds the models currently being checked. Reaching one of them again means the models are
+    /// recursive, which answers `false` rather than recursing forever.
+    fn implements_default_inner(&self, spec: &HirSpec, path: &mut Vec<String>) -> bool;
+    fn implements_dummy_inner(&self, spec: &HirSpec, path: &mut Vec<String>) -> bool;
+}
+
+/// Check a model, unless it's already on `path`.
+fn model_implements(name: &str, path: &mut Vec<String>, check: impl FnOnce(&mut Vec<String>) -> bool) -> bool {
+    if path.iter().any(|n| n == name) {
+        return false;
+    }
+    path.push(name.to_string());
+    let result = check(path);
+    path.pop();
+    result
 }
 
 impl ToRustType for Ty {
     fn to_rust_type(&self) -> TokenStream {
         match self {
             Ty::String => quote!(String),
-            Ty::Integer { .. } => quote!(i64),
-            Ty::Float => quote!(f64),
+            Ty::FormattedString(format) => string_format_type(*format),
+            Ty::Integer { size, .. } => integer_type(*size),
+            Ty::Float { size } => float_type(*size),
             Ty::Boolean => quote!(bool),
             Ty::Array(inner) => {
                 let inner = inner.to_rust_type();
@@ -43,8 +65,9 @@ impl ToRustType for Ty {
     fn to_reference_type(&self, specifier: TokenStream) -> TokenStream {
         match self {
             Ty::String => quote!(& #specifier str),
-            Ty::Integer { .. } => quote!(i64),
-            Ty::Float => quote!(f64),
+            Ty::FormattedString(format) => string_format_type(*format),
+            Ty::Integer { size, .. } => integer_type(*size),
+            Ty::Float { size } => float_type(*size),
             Ty::Boolean => quote!(bool),
             Ty::Array(inner) => {
                 if inner.is_reference_type() {
@@ -77,18 +100,51 @@ impl ToRustType for Ty {
     }
 }
 
+fn integer_type(size: IntegerSize) -> TokenStream {
+    match size {
+        IntegerSize::I8 => quote!(i8),
+        IntegerSize::I16 => quote!(i16),
+        IntegerSize::I32 => quote!(i32),
+        IntegerSize::I64 => quote!(i64),
+        IntegerSize::U8 => quote!(u8),
+        IntegerSize::U16 => quote!(u16),
+        IntegerSize::U32 => quote!(u32),
+        IntegerSize::U64 => quote!(u64),
+    }
+}
+
+fn string_format_type(format: StringFormat) -> TokenStream {
+    match format {
+        StringFormat::Uuid => quote!(uuid::Uuid),
+        StringFormat::Uri => quote!(url::Url),
+        StringFormat::Email => quote!(email_address::EmailAddress),
+        StringFormat::Ip => quote!(std::net::IpAddr),
+        StringFormat::Byte | StringFormat::Binary => quote!(Vec<u8>),
+        StringFormat::Duration => quote!(std::time::Duration),
+    }
+}
+
+fn float_type(size: FloatSize) -> TokenStream {
+    match size {
+        FloatSize::F32 => quote!(f32),
+        FloatSize::F64 => quote!(f64),
+    }
+}
+
 impl CanDerive for Ty {
-    fn implements_default(&self, spec: &HirSpec) -> bool {
+    fn implements_default_inner(&self, spec: &HirSpec, path: &mut Vec<String>) -> bool {
         match self {
             Ty::String => true,
+            Ty::FormattedString(format) => !matches!(format, StringFormat::Uri | StringFormat::Email | StringFormat::Ip),
             Ty::Integer { .. } => true,
-            Ty::Float => true,
+            Ty::Float { .. } => true,
             Ty::Boolean => true,
             Ty::Array(_) => true,
-            Ty::Model(name) => spec
-                .get_record(name.as_str())
-                .expect("Model not found")
-                .implements_default(spec),
+            Ty::Model(name) => model_implements(name, path, |path| {
+                spec.get_record(name.as_str())
+                    .expect("Model not found")
+                    .implements_default_inner(spec, path)
+            }),
             Ty::Unit => true,
             Ty::Any(_) => true,
             Ty::Date { .. } => true,
@@ -98,23 +154,24 @@ impl CanDerive for Ty {
         }
     }
 
-    fn implements_dummy(&self, spec: &HirSpec) -> bool {
+    fn implements_dummy_inner(&self, spec: &HirSpec, path: &mut Vec<String>) -> bool {
         match self {
             Ty::String => true,
+            Ty::FormattedString(_) => false,
             Ty::Integer { .. } => true,
-            Ty::Float => true,
+            Ty::Float { .. } => true,
             Ty::Boolean => true,
-            Ty::Array(inner) => inner.implements_dummy(spec),
-            Ty::Model(name) => {
+            Ty::Array(inner) => inner.implements_dummy_inner(spec, path),
+            Ty::Model(name) => model_implements(name, path, |path| {
                 let model = spec.get_record(name.as_str()).expect("Model not found");
-                model.fields().all(|f| f.ty.implements_dummy(spec))
-            }
+                model.fields().all(|f| f.ty.implements_dummy_inner(spec, path))
+            }),
             Ty::Unit => true,
             Ty::Any(_) => false,
             Ty::Date { .. } => true,
             Ty::DateTime => true,
             Ty::Currency { .. } => true,
-            Ty::HashMap(inner) => inner.implements_dummy(spec),
+            Ty::HashMap(inner) => inner.implements_dummy_inner(spec, path),
         }
     }
 }
@@ -0,0 +1,269 @@
+use std::collections::HashSet;
+
+use hir::{Constraints, HirField, HirSpec, Record};
+use mir::{Arg, Doc, Function, Ty, Visibility};
+use proc_macro2::TokenStream;
+use quote::quote;
+
+use crate::ToRustIdent;
+
+/// Whether the model gets a `validate()` method, because it or a model nested in it has constraints.
+fn record_needs_validation<'a>(name: &'a str, spec: &'a HirSpec, seen: &mut HashSet<&'a str>) -> bool {
+    if !seen.insert(name) {
+        return false;
+    }
+    let Ok(Record::Struct(s)) = spec.get_record(name) else {
+        return false;
+    };
+    s.fields
+        .values()
+        .any(|f| !field_checks(&f.ty, &f.constraints).is_empty() || ty_needs_validation(&f.ty, spec, seen))
+}
+
+/// Only models directly in the field, or one level inside a Vec or HashMap, are validated.
+fn ty_needs_validation<'a>(ty: &'a Ty, spec: &'a HirSpec, seen: &mut HashSet<&'a str>) -> bool {
+    match ty {
+        Ty::Model(name) => record_needs_validation(name, spec, seen),
+        Ty::Array(inner) | Ty::HashMap(inner) => match inner.as_ref() {
+            Ty::Model(name) => record_needs_validation(name, spec, seen),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+struct Check {
+    /// Statements the condition depends on, e.g. a static regex.
+    setup: TokenStream,
+    condition: TokenStream,
+    message: String,
+}
+
+fn literal(value: f64, ty: &Ty) -> TokenStream {
+    let s = match ty {
+        Ty::Integer { .. } => (value as i64).to_string(),
+        _ => format!("{:?}", value),
+    };
+    s.parse().unwrap()
+}
+
+fn display(value: f64, ty: &Ty) -> String {
+    match ty {
+        Ty::Integer { .. } => (value as i64).to_string(),
+        _ => value.to_string(),
+    }
+}
+
+/// Checks against `value`, a reference to the field value.
+fn field_checks(ty: &Ty, c: &Constraints) -> Vec<Check> {
+    let mut checks = Vec::new();
+    match ty {
+        Ty::String => {
+            if let Some(min) = c.min_length {
+                checks.push(Check {
+                    setup: TokenStream::new(),
+                    condition: quote! { value.chars().count() < #min },
+                    message: format!("must be at least {min} characters long"),
+                });
+            }
+            if let Some(max) = c.max_length {
+                checks.push(Check {
+                    setup: TokenStream::new(),
+                    condition: quote! { value.chars().count() > #max },
+                    message: format!("must be at most {max} characters long"),
+                });
+            }
+            if let Some(pattern) = &c.pattern {
+                checks.push(Check {
+                    setup: quote! {
+                        static PATTERN: std::sync::OnceLock<regex::Regex> = std::sync::OnceLock::new();
+                    },
+                    condition: quote! {
+                        !PATTERN.get_or_init(|| regex::Regex::new(#pattern).unwrap()).is_match(value)
+                    },
+                    message: format!("must match the pattern {pattern}"),
+                });
+            }
+        }
+        Ty::Integer { .. } | Ty::Float { .. } => {
+            // Skip bounds the type already guarantees, which would otherwise trip `unused_comparisons`.
+            let (lo, hi) = match ty {
+                Ty::Integer { size, .. } => {
+                    let (lo, hi) = size.range();
+                    (lo as f64, hi as f64)
+                }
+                _ => (f64::MIN, f64::MAX),
+            };
+            if let Some(min) = c.minimum.filter(|&m| m > lo || (c.exclusive_minimum && m >= lo)) {
+                let lit = literal(min, ty);
+                let n = display(min, ty);
+                checks.push(if c.exclusive_minimum {
+                    Check {
+                        setup: TokenStream::new(),
+                        condition: quote! { *value <= #lit },
+                        message: format!("must be greater than {n}"),
+                    }
+                } else {
+                    Check {
+                        setup: TokenStream::new(),
+                        condition: quote! { *value < #lit },
+                        message: format!("must be at least {n}"),
+                    }
+                });
+            }
+            if let Some(max) = c.maximum.filter(|&m| m < hi || (c.exclusive_maximum && m <= hi)) {
+                let lit = literal(max, ty);
+                let n = display(max, ty);
+                checks.push(if c.exclusive_maximum {
+                    Check {
+                        setup: TokenStream::new(),
+                        condition: quote! { *value >= #lit },
+                        message: format!("must be less than {n}"),
+                    }
+                } else {
+                    Check {
+                        setup: TokenStream::new(),
+                        condition: quote! { *value > #lit },
+                        message: format!("must be at most {n}"),
+                    }
+                });
+            }
+            if let Some(m) = c.multiple_of.filter(|&m| m > 0.0) {
+                let lit = literal(m, ty);
+                let condition = if matches!(ty, Ty::Integer { .. }) {
+                    quote! { *value % #lit != 0 }
+                } else {
+                    quote! {{
+                        let quotient = *value / #lit;
+                        (quotient - quotient.round()).abs() > 1e-9
+                    }}
+                };
+                checks.push(Check {
+                    setup: TokenStream::new(),
+                    condition,
+                    message: format!("must be a multiple of {}", display(m, ty)),
+                });
+            }
+        }
+        Ty::Array(inner) => {
+            if let Some(min) = c.min_items {
+                checks.push(Check {
+                    setup: TokenStream::new(),
+                    condition: quote! { value.len() < #min },
+                    message: format!("must have at least {min} items"),
+                });
+            }
+            if let Some(max) = c.max_items {
+                checks.push(Check {
+                    setup: TokenStream::new(),
+                    condition: quote! { value.len() > #max },
+                    message: format!("must have at most {max} items"),
+                });
+            }
+            // Models don't derive PartialEq, so uniqueness is only checked for primitives.
+            if c.unique_items && inner.is_primitive() {
+                checks.push(Check {
+                    setup: TokenStream::new(),
+                    condition: quote! { value.iter().enumerate().any(|(i, item)| value[..i].contains(item)) },
+                    message: "must not contain duplicate items".to_string(),
+                });
+            }
+        }
+        _ => {}
+    }
+    checks
+}
+
+fn nested_validation(ty: &Ty, spec: &HirSpec) -> TokenStream {
+    if !ty_needs_validation(ty, spec, &mut HashSet::new()) {
+        return TokenStream::new();
+    }
+    match ty {
+        Ty::Model(_) => quote! { value.validate()?; },
+        Ty::Array(_) => quote! {
+            for item in value {
+                item.validate()?;
+            }
+        },
+        Ty::HashMap(_) => quote! {
+            for item in value.values() {
+                item.validate()?;
+            }
+        },
+        _ => TokenStream::new(),
+    }
+}
+
+/// Build a `validate()` method checking each field against its schema constraints, or `None` if there
+/// is nothing to check. `fields` are (serialized name, field) pairs, where `optional` means the field
+/// is an `Option`.
+pub fn make_validate_method(fields: &[(String, HirField)], spec: &HirSpec) -> Option<Function<TokenStream>> {
+    let blocks = fields
+        .iter()
+        .filter_map(|(name, field)| {
+            let checks = field_checks(&field.ty, &field.constraints)
+                .into_iter()
+                .map(
+                    |Check {
+                         setup,
+                         condition,
+                         message,
+                     }| {
+                        let check = quote! {
+                            if #condition {
+                                return Err(crate::ValidationError { field: #name, message: #message });
+                            }
+                        };
+                        if setup.is_empty() {
+                            check
+                        } else {
+                            quote! {{
+                                #setup
+                                #check
+                            }}
+                        }
+                    },
+                )
+                .collect::<Vec<_>>();
+            let nested = nested_validation(&field.ty, spec);
+            if checks.is_empty() && nested.is_empty() {
+                return None;
+            }
+            let ident = name.to_rust_ident();
+            Some(if field.optional {
+                quote! {
+                    if let Some(value) = &self.#ident {
+                        #(#checks)*
+                        #nested
+                    }
+                }
+            } else {
+                quote! {
+                    let value = &self.#ident;
+                    #(#checks)*
+                    #nested
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    if blocks.is_empty() {
+        return None;
+    }
+    Some(Function {
+        name: "validate".to_rust_ident(),
+        doc: Some(Doc(
+            "Check the values against the constraints in the API spec.".to_string()
+        )),
+        args: vec![Arg::SelfArg {
+            mutable: false,
+            reference: true,
+        }],
+        ret: quote! { Result<(), crate::ValidationError> },
+        body: quote! {
+            #(#blocks)*
+            Ok(())
+        },
+        vis: Visibility::Public,
+        ..Function::default()
+    })
+}
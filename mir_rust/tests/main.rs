@@ -14,6 +14,13 @@ fn test_to_ident1() {
     assert_eq!(ident, "get_phone_checks_v0_1");
 }
 
+#[test]
+fn test_to_ident_punctuation() {
+    assert_eq!("Billing & Payments".to_rust_ident(), "billing_payments");
+    assert_eq!("Billing & Payments".to_rust_struct().0, "BillingPayments");
+    assert_eq!("Type".to_rust_ident(), "type_");
+}
+
 #[test]
 fn test_star() {
     let i = import!("super::*");